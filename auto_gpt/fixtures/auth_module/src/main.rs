@@ -0,0 +1,141 @@
+// Server wired up the way the backend prompt asks generated code to use the module
+#[path = "../../../../web_template/src/auth.rs"]
+mod auth;
+
+use actix_web::middleware::from_fn;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+#[derive(Deserialize)]
+struct Credentials {
+    username: String,
+    password: String,
+}
+
+// Id and password hash by username
+#[derive(Default)]
+struct Users(Mutex<HashMap<String, (u64, String)>>);
+
+async fn register(
+    users: web::Data<Users>,
+    credentials: web::Json<Credentials>,
+) -> actix_web::Result<HttpResponse> {
+    let hash = auth::hash_password(&credentials.password)?;
+    let mut users = users.0.lock().unwrap();
+    let id = users.len() as u64 + 1;
+    users.insert(credentials.username.clone(), (id, hash));
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "id": id })))
+}
+
+async fn login(
+    users: web::Data<Users>,
+    sessions: web::Data<auth::Sessions>,
+    credentials: web::Json<Credentials>,
+) -> impl Responder {
+    match users.0.lock().unwrap().get(&credentials.username) {
+        Some((id, hash)) if auth::verify_password(&credentials.password, hash) => {
+            HttpResponse::Ok().json(serde_json::json!({ "token": sessions.create(*id) }))
+        }
+        _ => HttpResponse::Unauthorized().finish(),
+    }
+}
+
+async fn logout(req: HttpRequest, sessions: web::Data<auth::Sessions>) -> impl Responder {
+    if let Some(token) = auth::bearer_token(&req) {
+        sessions.revoke(&token);
+    }
+    HttpResponse::Ok().finish()
+}
+
+async fn me(user: auth::AuthenticatedUser) -> impl Responder {
+    HttpResponse::Ok().json(serde_json::json!({ "id": user.user_id }))
+}
+
+fn routes(config: &mut web::ServiceConfig) {
+    config
+        .route("/register", web::post().to(register))
+        .route("/login", web::post().to(login))
+        .service(
+            web::scope("")
+                .wrap(from_fn(auth::require_session))
+                .route("/logout", web::post().to(logout))
+                .route("/me", web::get().to(me)),
+        );
+}
+
+fn main() {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::{header, StatusCode};
+    use actix_web::{test, App};
+
+    #[actix_web::test]
+    async fn test_session_lifecycle() {
+        let users = web::Data::new(Users::default());
+        let sessions = web::Data::new(auth::Sessions::new());
+        let app = test::init_service(
+            App::new()
+                .app_data(users)
+                .app_data(sessions)
+                .configure(routes),
+        )
+        .await;
+        let credentials = serde_json::json!({"username": "ada", "password": "hunter2"});
+        let wrong = serde_json::json!({"username": "ada", "password": "hunter3"});
+        let bearer = |token: &str| (header::AUTHORIZATION, format!("Bearer {}", token));
+
+        let anonymous = test::TestRequest::get().uri("/me").to_request();
+        assert_eq!(
+            test::call_service(&app, anonymous).await.status(),
+            StatusCode::UNAUTHORIZED
+        );
+
+        let register = test::TestRequest::post()
+            .uri("/register")
+            .set_json(&credentials)
+            .to_request();
+        assert!(test::call_service(&app, register).await.status().is_success());
+
+        let bad_login = test::TestRequest::post()
+            .uri("/login")
+            .set_json(&wrong)
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, bad_login).await.status(),
+            StatusCode::UNAUTHORIZED
+        );
+
+        let login = test::TestRequest::post()
+            .uri("/login")
+            .set_json(&credentials)
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, login).await;
+        let token = body["token"].as_str().expect("Login should return a token");
+
+        let me = test::TestRequest::get()
+            .uri("/me")
+            .insert_header(bearer(token))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, me).await;
+        assert_eq!(body["id"], 1);
+
+        let logout = test::TestRequest::post()
+            .uri("/logout")
+            .insert_header(bearer(token))
+            .to_request();
+        assert!(test::call_service(&app, logout).await.status().is_success());
+
+        let revoked = test::TestRequest::get()
+            .uri("/me")
+            .insert_header(bearer(token))
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, revoked).await.status(),
+            StatusCode::UNAUTHORIZED
+        );
+    }
+}
@@ -9,19 +9,29 @@ pub fn print_backend_webserver_code(_project_description_and_template: &str) {
     /// IMPORTANT: The following libraries are already installed
     ///   reqwest, serde, serde_json, tokio, actix-web, async-trait, actix_cors
     /// No other external libraries should be used. Write functions that fit with the description from the PROJECT_DESCRIPTION
+    /// IMPORTANT: The server binds 127.0.0.1 on the port in the PORT environment variable, like the CODE_TEMPLATE does
+    /// IMPORTANT: When a DATABASE_SCHEMA is given, the server stores exactly its entities, each with an integer id
+    ///   With DATABASE_STORE sqlite, rusqlite is also installed: at startup the server opens database.sqlite with rusqlite::Connection::open,
+    ///   runs conn.execute_batch(include_str!("../migrations/0001_init.sql")), keeps the connection in a Mutex in web::Data, and uses SQL queries
+    ///   that double quote table and column names like the MIGRATION does, e.g. SELECT "id" FROM "order"
+    ///   With DATABASE_STORE json, the Database struct of the CODE_TEMPLATE keeps one collection per entity in database.json
+    /// IMPORTANT: When an AUTH_MODULE is given, argon2 is also installed and the server uses the module for login, as USE THE AUTH MODULE says
     /// OUTPUT: Print ONLY the code, nothing else. This function ONLY prints code.
     println!(OUTPUT)
 }
 
 #[ai_function]
 pub fn print_improved_webserver_code(_project_description_and_template: &str) {
-    /// INPUT: Takes in a PROJECT_DESCRIPTION and CODE_TEMPLATE for a website backend build
+    /// INPUT: Takes in a PROJECT_DESCRIPTION, PROJECT_SCOPE and CODE_TEMPLATE for a website backend build
     /// FUNCTION: Performs the following tasks:
     ///   1. Removes any bugs in the code and adds minor additional functionality
     ///   2. Makes sure everything requested in the spec from a backend standpoint was followed. If not, add the feature. No code should be implemented later. Everything should be written now.
     ///   3. ONLY writes the code. No commentary.
     /// IMPORTANT: The following libraries are already installed. Does not use ANY libraries other than what was provided in the template
     ///   reqwest, serde, serde_json, tokio, actix-web, async-trait
+    /// IMPORTANT: Keeps binding 127.0.0.1 on the port in the PORT environment variable
+    /// IMPORTANT: Keeps persisting the entities of the DATABASE_SCHEMA the way the code already does, rusqlite is installed when it is used
+    /// IMPORTANT: Keeps `mod auth;`, the /register, /login and /logout routes and the require_session scope when the code has them
     println!(OUTPUT)
 }
 
@@ -29,6 +39,7 @@ pub fn print_improved_webserver_code(_project_description_and_template: &str) {
 pub fn print_fixed_code(_broken_code_with_bugs: &str) {
     /// INPUT: Takes in Rust BROKEN_CODE and the ERROR_BUGS found
     /// FUNCTION: Removes bugs from code
+    /// IMPORTANT: Never changes src/auth.rs, routes other than /register and /login stay behind auth::require_session
     /// IMPORTANT: Only prints out the new and improved code. No commentary or anything else
     println!(OUTPUT)
 }
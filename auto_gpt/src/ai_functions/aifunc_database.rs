@@ -0,0 +1,31 @@
+use ai_functions::ai_function;
+
+#[ai_function]
+pub fn print_database_schema(_project_description: &str) {
+    /// INPUT: Takes in a PROJECT_DESCRIPTION of a website that needs CRUD functionality
+    /// FUNCTION: Designs the entities the website's backend has to store and their fields
+    /// IMPORTANT: Every entity gets an integer "id" primary key automatically, do not list it
+    /// IMPORTANT: A field pointing to another entity has a "references" with that entity's name
+    /// IMPORTANT: Field types are one of: integer, real, text, boolean, datetime
+    /// OUTPUT: Prints ONLY the JSON schema, in this form:
+    /// {
+    ///   "entities": [
+    ///     {"name": "User", "fields": [{"name": "email", "type": "text"}]},
+    ///     {"name": "Task", "fields": [
+    ///       {"name": "title", "type": "text"},
+    ///       {"name": "done", "type": "boolean"},
+    ///       {"name": "due", "type": "datetime", "nullable": true},
+    ///       {"name": "user_id", "type": "integer", "references": "User"}
+    ///     ]}
+    ///   ]
+    /// }
+    println!(OUTPUT)
+}
+
+#[ai_function]
+pub fn print_fixed_database_schema(_broken_schema_with_problems: &str) {
+    /// INPUT: Takes in a PROJECT_DESCRIPTION, a BROKEN_SCHEMA of its entities and the PROBLEMS found in it
+    /// FUNCTION: Fixes the problems, keeping everything else of the schema
+    /// OUTPUT: Prints ONLY the fixed JSON schema, in the same form as the BROKEN_SCHEMA
+    println!(OUTPUT)
+}
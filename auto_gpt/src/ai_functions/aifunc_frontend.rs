@@ -0,0 +1,25 @@
+use ai_functions::ai_function;
+
+#[ai_function]
+pub fn print_frontend_code(_project_description_and_api_schema: &str) {
+    /// INPUT: Takes in a PROJECT_DESCRIPTION and the API_ENDPOINT_SCHEMA of the tested backend
+    /// FUNCTION: Writes a single page static frontend for the website in the PROJECT_DESCRIPTION
+    /// IMPORTANT: The page is ONE self contained index.html file with inline CSS and JavaScript. No build step, no frameworks, no CDN scripts.
+    /// IMPORTANT: The page starts its script with: const API_BASE_URL = window.API_BASE_URL || "http://localhost:8080";
+    /// IMPORTANT: Every request uses fetch(`${API_BASE_URL}/route`, { method: "METHOD", ... }) and ONLY calls routes and methods in the API_ENDPOINT_SCHEMA
+    /// IMPORTANT: Dynamic segments such as {id} are filled in the template string, e.g. fetch(`${API_BASE_URL}/task/${id}`, { method: "DELETE" })
+    /// IMPORTANT: Request bodies are JSON matching the "request_body" of the route, sent with the "Content-Type: application/json" header
+    /// IMPORTANT: When the schema has /register and /login, the page shows a login form first, keeps the "token" from /login
+    ///   and sends it as an "Authorization: Bearer <token>" header on every other request
+    /// OUTPUT: Print ONLY the HTML, nothing else. This function ONLY prints code.
+    println!(OUTPUT)
+}
+
+#[ai_function]
+pub fn print_fixed_frontend_code(_broken_html_with_issues: &str) {
+    /// INPUT: Takes in the HTML of a BROKEN_FRONTEND, the API_ENDPOINT_SCHEMA and the ISSUES found
+    /// FUNCTION: Fixes the issues, usually fetch calls to routes or methods that are not in the API_ENDPOINT_SCHEMA
+    /// IMPORTANT: Keeps the page a single self contained index.html using fetch(`${API_BASE_URL}/route`, { method: "METHOD" })
+    /// OUTPUT: Print ONLY the fixed HTML, nothing else. This function ONLY prints code.
+    println!(OUTPUT)
+}
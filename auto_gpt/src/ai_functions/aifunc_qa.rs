@@ -0,0 +1,25 @@
+use ai_functions::ai_function;
+
+#[ai_function]
+pub fn print_integration_tests(_backend_code_and_api_schema: &str) {
+    /// INPUT: Takes in the BACKEND_CODE of an actix-web server and the API_ENDPOINT_SCHEMA of its routes
+    /// FUNCTION: Writes the integration tests file tests/api.rs of the server's project
+    /// IMPORTANT: The file starts with: include!("../src/main.rs"); so every item of the BACKEND_CODE can be used directly
+    /// IMPORTANT: Each test is an #[actix_web::test] that builds the App with actix_web::test::init_service, using the same app_data, routes and services as main, and calls it with actix_web::test::TestRequest
+    /// IMPORTANT: When the BACKEND_CODE uses `mod auth;`, the App also gets web::Data::new(auth::Sessions::new()); tests register and log in first,
+    ///   send the token as an Authorization: Bearer header, and check that protected routes answer 401 without it
+    /// IMPORTANT: Tests check the status code and the JSON body of every route in the API_ENDPOINT_SCHEMA, e.g. that a created item is returned by the get route
+    /// IMPORTANT: The following libraries are already installed and no other may be used
+    ///   reqwest, serde, serde_json, tokio, actix-web, async-trait, actix_cors
+    /// OUTPUT: Print ONLY the code, nothing else. This function ONLY prints code.
+    println!(OUTPUT)
+}
+
+#[ai_function]
+pub fn print_fixed_integration_tests(_broken_tests_with_errors: &str) {
+    /// INPUT: Takes in the BACKEND_CODE, the BROKEN_TESTS of tests/api.rs and the ERROR_BUGS found compiling them
+    /// FUNCTION: Fixes the tests so they compile against the BACKEND_CODE, without changing what they check
+    /// IMPORTANT: Keeps include!("../src/main.rs"); at the start of the file
+    /// OUTPUT: Print ONLY the fixed code, nothing else. This function ONLY prints code.
+    println!(OUTPUT)
+}
@@ -1,82 +1,36 @@
-use crate::models::general::llm::{APIResponse, ChatCompletion, Message};
-use dotenv::dotenv;
-use reqwest::{
-    header::{HeaderMap, HeaderValue},
-    Client,
-};
-use std::env;
+use crate::apis::llm_provider::llm_provider;
+use crate::apis::streaming::TokenSink;
+use crate::models::general::error::LlmError;
+use crate::models::general::llm::{ChatResponse, Message};
 
-// Call LLM
-pub async fn call_gpt(messages: Vec<Message>) -> Result<String, Box<dyn std::error::Error + Send>> {
-    dotenv().ok();
-
-    // Extract LLM API information from environment variables - Azure OpenAI GPT4 specific
-    let api_key = env::var("AZURE_OPENAI_GPT4_KEY").expect("AZURE_OPENAI_GPT4_KEY must be set");
-    let api_version = env::var("AZURE_OPENAI_GPT4_API_VERSION")
-        .expect("AZURE_OPENAI_GPT4_API_VERSION must be set");
-    let endpoint =
-        env::var("AZURE_OPENAI_GPT4_ENDPOINT").expect("AZURE_OPENAI_GPT4_ENDPOINT must be set");
-    let deployment =
-        env::var("AZURE_OPENAI_GPT4_DEPLOYMENT").expect("AZURE_OPENAI_GPT4_DEPLOYMENT must be set");
-
-    // Confirm LLM url
-    let llm_url = format!(
-        "{}/openai/deployments/{}/chat/completions?api-version={}",
-        endpoint, deployment, api_version
-    );
-
-    // Create headers
-    let mut headers = HeaderMap::new();
-
-    // Headers - api key
-    headers.insert(
-        "api-key",
-        // use `unwrap` for now
-        HeaderValue::from_str(api_key.as_str())
-            .map_err(|e| -> Box<dyn std::error::Error + Send> { Box::new(e) })?,
-    );
-
-    // Create reqwest client
-    let client = Client::builder()
-        .default_headers(headers)
-        .build()
-        .map_err(|e| -> Box<dyn std::error::Error + Send> { Box::new(e) })?;
-
-    // Create chatcompletion
-    let chat_completion = ChatCompletion {
-        model: "gpt-4".to_string(),
-        messages,
-        temperature: 0.1,
-    };
-
-    // Trouble shoot errors from LLM API for now
-    // let res_raw = client
-    //     .post(llm_url)
-    //     .json(&chat_completion)
-    //     .send()
-    //     .await
-    //     .unwrap();
-
-    // dbg!(res_raw.text().await.unwrap());
+// Call LLM through the configured provider for an `#[ai_function]`
+// The response carries the prompt and completion tokens when the provider reports them
+pub async fn call_gpt(
+    function_name: &str,
+    messages: Vec<Message>,
+) -> Result<ChatResponse, LlmError> {
+    let provider = llm_provider()?;
+    provider.chat_for_function(function_name, messages).await
+}
 
-    // Extract API response
-    let res: APIResponse = client
-        .post(llm_url)
-        .json(&chat_completion)
-        .send()
-        .await
-        .map_err(|e| -> Box<dyn std::error::Error + Send> { Box::new(e) })?
-        .json()
+// Same as `call_gpt` but the completion is streamed, `on_token` sees each piece as it arrives
+pub async fn call_gpt_stream(
+    function_name: &str,
+    messages: Vec<Message>,
+    on_token: TokenSink<'_>,
+) -> Result<ChatResponse, LlmError> {
+    let provider = llm_provider()?;
+    provider
+        .chat_stream_for_function(function_name, messages, on_token)
         .await
-        .map_err(|e| -> Box<dyn std::error::Error + Send> { Box::new(e) })?;
-
-    // Send response
-    Ok(res.choices[0].message.content.clone())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::apis::llm_provider::with_llm_provider;
+    use crate::apis::mock_provider::MockProvider;
+    use std::sync::{Arc, Mutex};
 
     #[tokio::test]
     async fn test_call_gpt() {
@@ -86,14 +40,40 @@ mod tests {
         };
 
         let messages = vec![message];
-        let res = call_gpt(messages).await;
+        let provider = Arc::new(
+            MockProvider::new().with_response("convert_user_input_to_goal", "I am a mock"),
+        );
+        let res = with_llm_provider(
+            provider.clone(),
+            call_gpt("convert_user_input_to_goal", messages),
+        )
+        .await
+        .expect("Failed to call LLM");
+
+        assert_eq!(res.content, "I am a mock");
+        assert_eq!(provider.calls(), vec!["convert_user_input_to_goal"]);
+    }
+
+    #[tokio::test]
+    async fn test_call_gpt_stream() {
+        let messages = vec![Message {
+            role: "user".to_string(),
+            content: "Hello, who are you?".to_string(),
+        }];
+        let provider = Arc::new(
+            MockProvider::new().with_response("convert_user_input_to_goal", "I am a mock"),
+        );
+
+        let tokens = Mutex::new(vec![]);
+        let on_token = |token: &str| tokens.lock().unwrap().push(token.to_string());
+        let res = with_llm_provider(
+            provider,
+            call_gpt_stream("convert_user_input_to_goal", messages, &on_token),
+        )
+        .await
+        .expect("Failed to stream LLM response");
 
-        match res {
-            Ok(res_str) => {
-                dbg!(res_str);
-                assert!(true)
-            }
-            Err(_) => assert!(false),
-        }
+        assert_eq!(res.content, "I am a mock");
+        assert_eq!(*tokens.lock().unwrap(), vec!["I ", "am ", "a ", "mock"]);
     }
 }
@@ -0,0 +1,630 @@
+use crate::apis::mock_provider::{MockProvider, RecordingProvider};
+use crate::apis::streaming::{
+    parse_ndjson_line, parse_sse_line, LineBuffer, StreamCollector, StreamLineParser, TokenSink,
+};
+use crate::helpers::config::workspace_config;
+use crate::models::general::error::LlmError;
+use crate::models::general::llm::{
+    APIResponse, ChatCompletion, ChatResponse, Message, OllamaChat, OllamaResponse, StreamOptions,
+    TokenUsage,
+};
+use async_trait::async_trait;
+use dotenv::dotenv;
+use reqwest::{
+    header::{HeaderMap, HeaderValue, AUTHORIZATION, RETRY_AFTER},
+    Client, RequestBuilder, Response, StatusCode,
+};
+use serde::{de::DeserializeOwned, Deserialize};
+use std::env;
+use std::fmt::Debug;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, SystemTime};
+
+pub type LlmResult<T> = Result<T, LlmError>;
+
+const TEMPERATURE: f32 = 0.1;
+
+// Backend able to answer a chat completion request
+#[async_trait]
+pub trait LlmProvider: Debug + Send + Sync {
+    // Short name used in logs and configuration
+    fn name(&self) -> &str;
+
+    // Model answering the requests, used to price token usage
+    fn model(&self) -> &str;
+
+    // Send messages to LLM and return the content of the first choice
+    async fn chat(&self, messages: Vec<Message>) -> LlmResult<ChatResponse>;
+
+    // Same as `chat`, tagged with the `#[ai_function]` name that built the prompt
+    async fn chat_for_function(
+        &self,
+        function_name: &str,
+        messages: Vec<Message>,
+    ) -> LlmResult<ChatResponse> {
+        let _ = function_name;
+        self.chat(messages).await
+    }
+
+    // Stream the response, passing each token to `on_token` as it arrives
+    // Providers without streaming send the whole response as a single token
+    async fn chat_stream(
+        &self,
+        messages: Vec<Message>,
+        on_token: TokenSink<'_>,
+    ) -> LlmResult<ChatResponse> {
+        let response = self.chat(messages).await?;
+        on_token(&response.content);
+        Ok(response)
+    }
+
+    // Same as `chat_stream`, tagged with the `#[ai_function]` name that built the prompt
+    async fn chat_stream_for_function(
+        &self,
+        function_name: &str,
+        messages: Vec<Message>,
+        on_token: TokenSink<'_>,
+    ) -> LlmResult<ChatResponse> {
+        let _ = function_name;
+        self.chat_stream(messages, on_token).await
+    }
+}
+
+// Which provider to use and how to reach it
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "provider", rename_all = "lowercase")]
+pub enum LlmConfig {
+    Azure {
+        api_key: String,
+        endpoint: String,
+        deployment: String,
+        api_version: String,
+        // Model behind the deployment, used to price its calls
+        #[serde(default = "default_azure_model")]
+        model: String,
+    },
+    OpenAI {
+        api_key: Option<String>,
+        base_url: String,
+        model: String,
+    },
+    Ollama {
+        base_url: String,
+        model: String,
+    },
+    Mock {
+        fixtures_dir: PathBuf,
+    },
+}
+
+fn default_azure_model() -> String {
+    "gpt-4".to_string()
+}
+
+fn required_var(name: &str) -> LlmResult<String> {
+    env::var(name).map_err(|_| LlmError::Config(format!("{} must be set", name)))
+}
+
+fn var_or(name: &str, default: &str) -> String {
+    env::var(name).unwrap_or_else(|_| default.to_string())
+}
+
+impl LlmConfig {
+    // Read provider settings from environment variables (and .env)
+    // LLM_PROVIDER selects the backend and defaults to azure
+    pub fn from_env() -> LlmResult<Self> {
+        dotenv().ok();
+        Self::for_provider(&var_or("LLM_PROVIDER", "azure"))
+    }
+
+    // Settings for a named provider, read from its environment variables
+    pub fn for_provider(provider: &str) -> LlmResult<Self> {
+        dotenv().ok();
+
+        match provider.to_lowercase().as_str() {
+            "azure" => Ok(Self::Azure {
+                api_key: required_var("AZURE_OPENAI_GPT4_KEY")?,
+                endpoint: required_var("AZURE_OPENAI_GPT4_ENDPOINT")?,
+                deployment: required_var("AZURE_OPENAI_GPT4_DEPLOYMENT")?,
+                api_version: required_var("AZURE_OPENAI_GPT4_API_VERSION")?,
+                model: env::var("AZURE_OPENAI_MODEL").unwrap_or_else(|_| default_azure_model()),
+            }),
+            "openai" => Ok(Self::OpenAI {
+                api_key: env::var("OPENAI_API_KEY").ok(),
+                base_url: var_or("OPENAI_BASE_URL", "https://api.openai.com/v1"),
+                model: var_or("OPENAI_MODEL", "gpt-4"),
+            }),
+            "ollama" => Ok(Self::Ollama {
+                base_url: var_or("OLLAMA_BASE_URL", "http://localhost:11434"),
+                model: var_or("OLLAMA_MODEL", "llama3"),
+            }),
+            "mock" => Ok(Self::Mock {
+                fixtures_dir: PathBuf::from(required_var("MOCK_LLM_FIXTURES_DIR")?),
+            }),
+            other => Err(LlmError::Config(format!("Unknown LLM_PROVIDER: {}", other))),
+        }
+    }
+}
+
+// Build a provider from its configuration
+pub fn build_provider(config: &LlmConfig) -> LlmResult<Arc<dyn LlmProvider>> {
+    let provider: Arc<dyn LlmProvider> = match config.clone() {
+        LlmConfig::Azure {
+            api_key,
+            endpoint,
+            deployment,
+            api_version,
+            model,
+        } => Arc::new(AzureOpenAIProvider::new(
+            api_key,
+            endpoint,
+            deployment,
+            api_version,
+            model,
+        )?),
+        LlmConfig::OpenAI {
+            api_key,
+            base_url,
+            model,
+        } => Arc::new(OpenAIProvider::new(api_key, base_url, model)?),
+        LlmConfig::Ollama { base_url, model } => Arc::new(OllamaProvider::new(base_url, model)?),
+        LlmConfig::Mock { fixtures_dir } => Arc::new(MockProvider::from_fixtures(fixtures_dir)),
+    };
+
+    // Optionally record every response so it can be replayed by the mock provider
+    match env::var("LLM_RECORD_DIR") {
+        Ok(record_dir) => Ok(Arc::new(RecordingProvider::new(
+            provider,
+            PathBuf::from(record_dir),
+        ))),
+        Err(_) => Ok(provider),
+    }
+}
+
+static LLM_PROVIDER: OnceLock<Arc<dyn LlmProvider>> = OnceLock::new();
+
+tokio::task_local! {
+    static SCOPED_LLM_PROVIDER: Arc<dyn LlmProvider>;
+}
+
+// Provider shared by all agents - built from configuration on first use
+// A provider set with `with_llm_provider` takes precedence
+pub fn llm_provider() -> LlmResult<Arc<dyn LlmProvider>> {
+    if let Ok(provider) = SCOPED_LLM_PROVIDER.try_with(|provider| provider.clone()) {
+        return Ok(provider);
+    }
+    if let Some(provider) = LLM_PROVIDER.get() {
+        return Ok(provider.clone());
+    }
+    let llm_config = workspace_config()
+        .map_err(|e| LlmError::Config(e.to_string()))?
+        .llm_config()?;
+    let provider = build_provider(&llm_config)?;
+    Ok(LLM_PROVIDER.get_or_init(|| provider).clone())
+}
+
+// Run a future with every LLM call routed to the given provider
+#[cfg(test)]
+pub async fn with_llm_provider<F: std::future::Future>(
+    provider: Arc<dyn LlmProvider>,
+    f: F,
+) -> F::Output {
+    SCOPED_LLM_PROVIDER.scope(provider, f).await
+}
+
+fn client_with_headers(headers: HeaderMap) -> LlmResult<Client> {
+    Client::builder()
+        .default_headers(headers)
+        .build()
+        .map_err(|e| LlmError::Config(e.to_string()))
+}
+
+fn header_value(value: &str) -> LlmResult<HeaderValue> {
+    HeaderValue::from_str(value).map_err(|e| LlmError::Config(e.to_string()))
+}
+
+// Read `Retry-After` (seconds or an HTTP date) or Azure's `retry-after-ms`
+fn retry_after(response: &Response) -> Option<Duration> {
+    let header = |name| response.headers().get(name)?.to_str().ok();
+    header("retry-after-ms")
+        .and_then(|ms| ms.trim().parse::<u64>().ok())
+        .map(Duration::from_millis)
+        .or_else(|| parse_retry_after(header(RETRY_AFTER.as_str())?, SystemTime::now()))
+}
+
+// A date that has already passed means retry straight away
+fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(date.duration_since(now).unwrap_or_default())
+}
+
+// Map a non success HTTP status to an error
+async fn status_error(response: Response) -> LlmError {
+    let status = response.status();
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        return LlmError::RateLimited {
+            retry_after: retry_after(&response),
+        };
+    }
+
+    let body = response.text().await.unwrap_or_default();
+    if status.is_server_error() {
+        LlmError::Server {
+            status: status.as_u16(),
+            body,
+        }
+    } else {
+        LlmError::Client {
+            status: status.as_u16(),
+            body,
+        }
+    }
+}
+
+// Send a request and decode its JSON body
+async fn send_json<T: DeserializeOwned>(request: RequestBuilder) -> LlmResult<T> {
+    let response = request
+        .send()
+        .await
+        .map_err(|e| LlmError::Transport(e.to_string()))?;
+
+    if !response.status().is_success() {
+        return Err(status_error(response).await);
+    }
+
+    response
+        .json()
+        .await
+        .map_err(|e| LlmError::InvalidResponse(e.to_string()))
+}
+
+// Send a streaming request and feed its body to `parse_line`, one line at a time
+async fn send_streaming(
+    request: RequestBuilder,
+    parse_line: StreamLineParser,
+    on_token: TokenSink<'_>,
+) -> LlmResult<ChatResponse> {
+    let mut response = request
+        .send()
+        .await
+        .map_err(|e| LlmError::Transport(e.to_string()))?;
+
+    if !response.status().is_success() {
+        return Err(status_error(response).await);
+    }
+
+    let mut collector = StreamCollector::new(on_token);
+    let mut buffer = LineBuffer::default();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| LlmError::Transport(e.to_string()))?
+    {
+        for line in buffer.push(&chunk) {
+            parse_line(&line, &mut collector)?;
+        }
+    }
+    if let Some(line) = buffer.finish() {
+        parse_line(&line, &mut collector)?;
+    }
+    Ok(collector.finish())
+}
+
+// Post an OpenAI style chat completion and extract the first choice
+async fn post_chat_completion(
+    client: &Client,
+    url: &str,
+    chat_completion: &ChatCompletion,
+) -> LlmResult<ChatResponse> {
+    let res: APIResponse = send_json(client.post(url).json(chat_completion)).await?;
+
+    let content = res
+        .choices
+        .into_iter()
+        .next()
+        .map(|choice| choice.message.content)
+        .ok_or_else(|| {
+            LlmError::InvalidResponse("LLM response contained no choices".to_string())
+        })?;
+    Ok(ChatResponse {
+        content,
+        usage: res.usage,
+    })
+}
+
+// Azure OpenAI deployment
+#[derive(Debug)]
+pub struct AzureOpenAIProvider {
+    client: Client,
+    url: String,
+    model: String,
+}
+
+impl AzureOpenAIProvider {
+    pub fn new(
+        api_key: String,
+        endpoint: String,
+        deployment: String,
+        api_version: String,
+        model: String,
+    ) -> LlmResult<Self> {
+        let url = format!(
+            "{}/openai/deployments/{}/chat/completions?api-version={}",
+            endpoint, deployment, api_version
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert("api-key", header_value(api_key.as_str())?);
+
+        Ok(Self {
+            client: client_with_headers(headers)?,
+            url,
+            model,
+        })
+    }
+}
+
+#[async_trait]
+impl LlmProvider for AzureOpenAIProvider {
+    fn name(&self) -> &str {
+        "azure"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn chat(&self, messages: Vec<Message>) -> LlmResult<ChatResponse> {
+        let chat_completion = ChatCompletion {
+            model: self.model.clone(),
+            messages,
+            temperature: TEMPERATURE,
+            stream: false,
+            stream_options: None,
+        };
+        post_chat_completion(&self.client, &self.url, &chat_completion).await
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: Vec<Message>,
+        on_token: TokenSink<'_>,
+    ) -> LlmResult<ChatResponse> {
+        let chat_completion = ChatCompletion {
+            model: self.model.clone(),
+            messages,
+            temperature: TEMPERATURE,
+            stream: true,
+            // Only newer api versions accept stream_options, usage is estimated instead
+            stream_options: None,
+        };
+        let request = self.client.post(&self.url).json(&chat_completion);
+        send_streaming(request, parse_sse_line, on_token).await
+    }
+}
+
+// Any OpenAI compatible endpoint - api.openai.com, vLLM, llama.cpp server, ...
+#[derive(Debug)]
+pub struct OpenAIProvider {
+    client: Client,
+    url: String,
+    model: String,
+}
+
+impl OpenAIProvider {
+    pub fn new(api_key: Option<String>, base_url: String, model: String) -> LlmResult<Self> {
+        let mut headers = HeaderMap::new();
+        if let Some(api_key) = api_key {
+            headers.insert(
+                AUTHORIZATION,
+                header_value(format!("Bearer {}", api_key).as_str())?,
+            );
+        }
+
+        Ok(Self {
+            client: client_with_headers(headers)?,
+            url: format!("{}/chat/completions", base_url.trim_end_matches('/')),
+            model,
+        })
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OpenAIProvider {
+    fn name(&self) -> &str {
+        "openai"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn chat(&self, messages: Vec<Message>) -> LlmResult<ChatResponse> {
+        let chat_completion = ChatCompletion {
+            model: self.model.clone(),
+            messages,
+            temperature: TEMPERATURE,
+            stream: false,
+            stream_options: None,
+        };
+        post_chat_completion(&self.client, &self.url, &chat_completion).await
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: Vec<Message>,
+        on_token: TokenSink<'_>,
+    ) -> LlmResult<ChatResponse> {
+        let chat_completion = ChatCompletion {
+            model: self.model.clone(),
+            messages,
+            temperature: TEMPERATURE,
+            stream: true,
+            stream_options: Some(StreamOptions {
+                include_usage: true,
+            }),
+        };
+        let request = self.client.post(&self.url).json(&chat_completion);
+        send_streaming(request, parse_sse_line, on_token).await
+    }
+}
+
+// Local Ollama server using its native chat API
+#[derive(Debug)]
+pub struct OllamaProvider {
+    client: Client,
+    url: String,
+    model: String,
+}
+
+impl OllamaProvider {
+    pub fn new(base_url: String, model: String) -> LlmResult<Self> {
+        Ok(Self {
+            client: client_with_headers(HeaderMap::new())?,
+            url: format!("{}/api/chat", base_url.trim_end_matches('/')),
+            model,
+        })
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OllamaProvider {
+    fn name(&self) -> &str {
+        "ollama"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn chat(&self, messages: Vec<Message>) -> LlmResult<ChatResponse> {
+        let chat = OllamaChat::new(self.model.clone(), messages, TEMPERATURE, false);
+
+        let res: OllamaResponse = send_json(self.client.post(&self.url).json(&chat)).await?;
+
+        let usage = match (res.prompt_eval_count, res.eval_count) {
+            (Some(prompt_tokens), Some(completion_tokens)) => Some(TokenUsage {
+                prompt_tokens,
+                completion_tokens,
+            }),
+            _ => None,
+        };
+        Ok(ChatResponse {
+            content: res.message.content,
+            usage,
+        })
+    }
+    async fn chat_stream(
+        &self,
+        messages: Vec<Message>,
+        on_token: TokenSink<'_>,
+    ) -> LlmResult<ChatResponse> {
+        let chat = OllamaChat::new(self.model.clone(), messages, TEMPERATURE, true);
+        let request = self.client.post(&self.url).json(&chat);
+        send_streaming(request, parse_ndjson_line, on_token).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_provider_from_config() {
+        let config = LlmConfig::Ollama {
+            base_url: "http://localhost:11434/".to_string(),
+            model: "llama3".to_string(),
+        };
+        let provider = build_provider(&config).expect("Failed to build provider");
+        assert_eq!(provider.name(), "ollama");
+
+        // Azure calls are priced as the deployment's model
+        let config: LlmConfig = toml::from_str(
+            r#"
+            provider = "azure"
+            api_key = "key"
+            endpoint = "https://example.openai.azure.com"
+            deployment = "chat"
+            api_version = "2024-08-01-preview"
+            model = "gpt-4o"
+            "#,
+        )
+        .expect("Invalid azure config");
+        let provider = build_provider(&config).expect("Failed to build provider");
+        assert_eq!(provider.model(), "gpt-4o");
+    }
+
+    #[tokio::test]
+    async fn test_openai_stream_requests_usage() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = vec![];
+            let mut buf = [0; 4096];
+            // The JSON body ends the request
+            while !request.ends_with(b"}") {
+                let n = stream.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            let body = concat!(
+                "data: {\"choices\":[{\"delta\":{\"content\":\"hi\"}}]}\n\n",
+                "data: {\"choices\":[],\"usage\":{\"prompt_tokens\":9,\"completion_tokens\":1}}\n\n",
+                "data: [DONE]\n\n",
+            );
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        let provider = OpenAIProvider::new(None, base_url, "gpt-4o".to_string()).unwrap();
+        let response = provider
+            .chat_stream(vec![], &|_: &str| {})
+            .await
+            .expect("Stream failed");
+        let request = server.await.unwrap();
+
+        assert!(
+            request.contains(r#""stream_options":{"include_usage":true}"#),
+            "{}",
+            request
+        );
+        assert_eq!(response.content, "hi");
+        assert_eq!(
+            response.usage,
+            Some(TokenUsage {
+                prompt_tokens: 9,
+                completion_tokens: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_retry_after_seconds_or_http_date() {
+        let now = httpdate::parse_http_date("Wed, 21 Oct 2015 07:28:00 GMT").unwrap();
+        assert_eq!(
+            parse_retry_after(" 120 ", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:30:00 GMT", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:00:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+}
@@ -0,0 +1,250 @@
+use crate::apis::llm_provider::{LlmProvider, LlmResult};
+use crate::apis::streaming::TokenSink;
+use crate::models::general::error::LlmError;
+use crate::models::general::llm::{ChatResponse, Message};
+use async_trait::async_trait;
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+// Key used when the provider is called without an `#[ai_function]` name
+pub const PLAIN_CHAT_KEY: &str = "chat";
+
+// Deterministic provider for offline runs and tests
+// Responses are keyed on the `#[ai_function]` name passed through `get_function_string!`
+// Scripted responses are served first, in order - the last one repeats
+// Otherwise `<fixtures_dir>/<function_name>.<ext>` is returned
+#[derive(Debug, Default)]
+pub struct MockProvider {
+    fixtures_dir: Option<PathBuf>,
+    scripted: Mutex<HashMap<String, VecDeque<String>>>,
+    calls: Mutex<Vec<String>>,
+    messages: Mutex<Vec<Vec<Message>>>,
+}
+
+impl MockProvider {
+    #[cfg(test)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_fixtures(fixtures_dir: PathBuf) -> Self {
+        Self {
+            fixtures_dir: Some(fixtures_dir),
+            ..Self::default()
+        }
+    }
+
+    // Queue a canned response for a function
+    #[cfg(test)]
+    pub fn with_response(self, function_name: &str, response: &str) -> Self {
+        self.scripted
+            .lock()
+            .unwrap()
+            .entry(function_name.to_string())
+            .or_default()
+            .push_back(response.to_string());
+        self
+    }
+
+    // Names of the functions called so far, in order
+    #[cfg(test)]
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    // Messages of every call so far, in order
+    #[cfg(test)]
+    pub fn messages(&self) -> Vec<Vec<Message>> {
+        self.messages.lock().unwrap().clone()
+    }
+
+    fn scripted_response(&self, function_name: &str) -> Option<String> {
+        let mut scripted = self.scripted.lock().unwrap();
+        let queue = scripted.get_mut(function_name)?;
+        if queue.len() > 1 {
+            queue.pop_front()
+        } else {
+            queue.front().cloned()
+        }
+    }
+
+    fn fixture_response(&self, function_name: &str) -> Option<String> {
+        let fixtures_dir = self.fixtures_dir.as_ref()?;
+        let mut fixtures: Vec<PathBuf> = fs::read_dir(fixtures_dir)
+            .ok()?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.file_stem().is_some_and(|stem| stem == function_name))
+            .collect();
+        fixtures.sort();
+        fs::read_to_string(fixtures.first()?).ok()
+    }
+}
+
+#[async_trait]
+impl LlmProvider for MockProvider {
+    fn name(&self) -> &str {
+        "mock"
+    }
+
+    fn model(&self) -> &str {
+        "mock"
+    }
+
+    async fn chat(&self, messages: Vec<Message>) -> LlmResult<ChatResponse> {
+        self.chat_for_function(PLAIN_CHAT_KEY, messages).await
+    }
+
+    async fn chat_for_function(
+        &self,
+        function_name: &str,
+        messages: Vec<Message>,
+    ) -> LlmResult<ChatResponse> {
+        self.calls.lock().unwrap().push(function_name.to_string());
+        self.messages.lock().unwrap().push(messages);
+
+        // No usage is reported, so token counts are estimated like any provider without one
+        self.scripted_response(function_name)
+            .or_else(|| self.fixture_response(function_name))
+            .map(|content| ChatResponse {
+                content,
+                usage: None,
+            })
+            .ok_or_else(|| LlmError::Config(format!("No mock LLM response for {}", function_name)))
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: Vec<Message>,
+        on_token: TokenSink<'_>,
+    ) -> LlmResult<ChatResponse> {
+        self.chat_stream_for_function(PLAIN_CHAT_KEY, messages, on_token)
+            .await
+    }
+
+    // Replays the response word by word, like a real stream
+    async fn chat_stream_for_function(
+        &self,
+        function_name: &str,
+        messages: Vec<Message>,
+        on_token: TokenSink<'_>,
+    ) -> LlmResult<ChatResponse> {
+        let response = self.chat_for_function(function_name, messages).await?;
+        for token in response.content.split_inclusive(char::is_whitespace) {
+            on_token(token);
+        }
+        Ok(response)
+    }
+}
+
+// Wraps a real provider and saves each response as a fixture for `MockProvider`
+#[derive(Debug)]
+pub struct RecordingProvider {
+    inner: Arc<dyn LlmProvider>,
+    record_dir: PathBuf,
+}
+
+impl RecordingProvider {
+    pub fn new(inner: Arc<dyn LlmProvider>, record_dir: PathBuf) -> Self {
+        Self { inner, record_dir }
+    }
+
+    fn record(&self, function_name: &str, response: &str) {
+        let path = self.record_dir.join(format!("{}.txt", function_name));
+        if let Err(e) =
+            fs::create_dir_all(&self.record_dir).and_then(|_| fs::write(&path, response))
+        {
+            println!("Failed to record LLM response to {}: {}", path.display(), e);
+        }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for RecordingProvider {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+
+    async fn chat(&self, messages: Vec<Message>) -> LlmResult<ChatResponse> {
+        self.chat_for_function(PLAIN_CHAT_KEY, messages).await
+    }
+
+    async fn chat_for_function(
+        &self,
+        function_name: &str,
+        messages: Vec<Message>,
+    ) -> LlmResult<ChatResponse> {
+        let response = self
+            .inner
+            .chat_for_function(function_name, messages)
+            .await?;
+        self.record(function_name, &response.content);
+        Ok(response)
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: Vec<Message>,
+        on_token: TokenSink<'_>,
+    ) -> LlmResult<ChatResponse> {
+        self.chat_stream_for_function(PLAIN_CHAT_KEY, messages, on_token)
+            .await
+    }
+
+    async fn chat_stream_for_function(
+        &self,
+        function_name: &str,
+        messages: Vec<Message>,
+        on_token: TokenSink<'_>,
+    ) -> LlmResult<ChatResponse> {
+        let response = self
+            .inner
+            .chat_stream_for_function(function_name, messages, on_token)
+            .await?;
+        self.record(function_name, &response.content);
+        Ok(response)
+    }
+}
+
+// Fixtures shipped with the crate - enough for a full offline pipeline run
+#[cfg(test)]
+pub fn fixture_provider() -> Arc<dyn LlmProvider> {
+    Arc::new(MockProvider::from_fixtures(PathBuf::from(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/fixtures/llm"
+    ))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_mock_provider_scripted_then_fixture() {
+        let provider = MockProvider::from_fixtures(PathBuf::from(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/fixtures/llm"
+        )))
+        .with_response("print_site_urls", "[]")
+        .with_response("print_site_urls", "[\"https://example.com\"]");
+
+        let first = provider.chat_for_function("print_site_urls", vec![]).await;
+        let second = provider.chat_for_function("print_site_urls", vec![]).await;
+        let third = provider.chat_for_function("print_site_urls", vec![]).await;
+        assert_eq!(first.unwrap().content, "[]");
+        assert_eq!(second.unwrap(), third.unwrap());
+
+        let scope = provider
+            .chat_for_function("print_project_scope", vec![])
+            .await;
+        assert!(scope.unwrap().content.contains("is_crud_required"));
+
+        assert!(provider.chat_for_function("unknown", vec![]).await.is_err());
+        assert_eq!(provider.calls().len(), 5);
+    }
+}
@@ -1 +1,6 @@
 pub mod call_request;
+pub mod llm_provider;
+pub mod mock_provider;
+pub mod retry;
+pub mod streaming;
+pub mod usage;
@@ -0,0 +1,326 @@
+use crate::models::general::error::{AutoGptError, LlmError};
+use rand::Rng;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::future::Future;
+use std::str::FromStr;
+use std::time::Duration;
+
+// How often and how patiently an LLM call is retried
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    pub multiplier: f64,
+    // Fraction of the backoff randomly added or removed, 0.0 - 1.0
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff_ms: 1_000,
+            max_backoff_ms: 30_000,
+            multiplier: 2.0,
+            jitter: 0.2,
+        }
+    }
+}
+
+// Fields of the `[retry]` table that one agent overrides
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct RetryOverride {
+    pub max_attempts: Option<u32>,
+    pub initial_backoff_ms: Option<u64>,
+    pub max_backoff_ms: Option<u64>,
+    pub multiplier: Option<f64>,
+    pub jitter: Option<f64>,
+}
+
+// `[retry]` table, `[retry.agents.backend_developer]` overrides it for the Backend Developer only
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    #[serde(flatten)]
+    pub policy: RetryPolicy,
+    // Keyed on the agent position, matched like its environment variable suffix
+    pub agents: HashMap<String, RetryOverride>,
+}
+
+// Environment variable suffix for an agent position - "Backend Developer" -> "BACKEND_DEVELOPER"
+fn agent_suffix(agent_position: &str) -> String {
+    agent_position
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join("_")
+        .to_uppercase()
+}
+
+fn parse_env<T: FromStr>(name: &str, value: &str) -> Result<T, AutoGptError>
+where
+    T::Err: Display,
+{
+    value
+        .trim()
+        .parse()
+        .map_err(|e| AutoGptError::Config(format!("{}: {}", name, e)))
+}
+
+impl RetryOverride {
+    fn apply(&self, policy: &mut RetryPolicy) {
+        policy.max_attempts = self.max_attempts.unwrap_or(policy.max_attempts);
+        policy.initial_backoff_ms = self.initial_backoff_ms.unwrap_or(policy.initial_backoff_ms);
+        policy.max_backoff_ms = self.max_backoff_ms.unwrap_or(policy.max_backoff_ms);
+        policy.multiplier = self.multiplier.unwrap_or(policy.multiplier);
+        policy.jitter = self.jitter.unwrap_or(policy.jitter);
+    }
+
+    // One `LLM_RETRY_*` value, `field` is the upper case name of the setting
+    fn set(&mut self, field: &str, name: &str, value: &str) -> Result<(), AutoGptError> {
+        match field {
+            "MAX_ATTEMPTS" => self.max_attempts = Some(parse_env(name, value)?),
+            "INITIAL_BACKOFF_MS" => self.initial_backoff_ms = Some(parse_env(name, value)?),
+            "MAX_BACKOFF_MS" => self.max_backoff_ms = Some(parse_env(name, value)?),
+            "MULTIPLIER" => self.multiplier = Some(parse_env(name, value)?),
+            "JITTER" => self.jitter = Some(parse_env(name, value)?),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+const RETRY_ENV_FIELDS: [&str; 5] = [
+    "MAX_ATTEMPTS",
+    "INITIAL_BACKOFF_MS",
+    "MAX_BACKOFF_MS",
+    "MULTIPLIER",
+    "JITTER",
+];
+
+impl RetryConfig {
+    // LLM_RETRY_MAX_ATTEMPTS sets the value for every agent,
+    // LLM_RETRY_MAX_ATTEMPTS_BACKEND_DEVELOPER for the Backend Developer only
+    pub fn apply_env(
+        &mut self,
+        vars: impl Iterator<Item = (String, String)>,
+    ) -> Result<(), AutoGptError> {
+        for (name, value) in vars {
+            let Some(setting) = name.strip_prefix("LLM_RETRY_") else {
+                continue;
+            };
+            for field in RETRY_ENV_FIELDS {
+                let Some(rest) = setting.strip_prefix(field) else {
+                    continue;
+                };
+                if rest.is_empty() {
+                    let mut global = RetryOverride::default();
+                    global.set(field, &name, &value)?;
+                    global.apply(&mut self.policy);
+                } else if let Some(agent) = rest.strip_prefix('_') {
+                    self.agents
+                        .entry(agent.to_string())
+                        .or_default()
+                        .set(field, &name, &value)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Policy of an agent, the `[retry]` values with its own overrides on top
+    pub fn for_agent(&self, agent_position: &str) -> RetryPolicy {
+        let suffix = agent_suffix(agent_position);
+        let mut policy = self.policy.clone();
+        for (agent, overrides) in &self.agents {
+            if agent_suffix(agent) == suffix {
+                overrides.apply(&mut policy);
+            }
+        }
+        policy.max_attempts = policy.max_attempts.max(1);
+        policy
+    }
+}
+
+impl RetryPolicy {
+    // Exponential backoff before the given retry (1 = first retry), without jitter
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let exp = self.multiplier.powi(retry.saturating_sub(1) as i32);
+        let millis = (self.initial_backoff_ms as f64 * exp).min(self.max_backoff_ms as f64);
+        Duration::from_millis(millis as u64)
+    }
+
+    // Delay before the given retry - a provider supplied Retry-After wins
+    // Neither it nor the jittered backoff goes past max_backoff_ms
+    pub fn delay_for(&self, retry: u32, error: &LlmError) -> Duration {
+        let max_backoff = Duration::from_millis(self.max_backoff_ms);
+        if let LlmError::RateLimited {
+            retry_after: Some(retry_after),
+        } = error
+        {
+            return (*retry_after).min(max_backoff);
+        }
+
+        let backoff = self.backoff(retry);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        if jitter == 0.0 {
+            return backoff;
+        }
+        let factor = rand::rng().random_range((1.0 - jitter)..=(1.0 + jitter));
+        backoff.mul_f64(factor).min(max_backoff)
+    }
+}
+
+// Run an LLM call until it succeeds, fails permanently or runs out of attempts
+// `on_retry` is told about each failure that is going to be retried
+pub async fn with_retry<T, F, Fut>(
+    policy: &RetryPolicy,
+    mut call: F,
+    mut on_retry: impl FnMut(&LlmError, Duration),
+) -> Result<T, LlmError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, LlmError>>,
+{
+    let mut attempt = 1;
+    loop {
+        match call().await {
+            Ok(res) => return Ok(res),
+            Err(e) if e.is_retryable() && attempt < policy.max_attempts => {
+                let delay = policy.delay_for(attempt, &e);
+                on_retry(&e, delay);
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn instant_policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            initial_backoff_ms: 0,
+            jitter: 0.0,
+            ..RetryPolicy::default()
+        }
+    }
+
+    #[test]
+    fn test_backoff_is_exponential_and_capped() {
+        let policy = RetryPolicy {
+            jitter: 0.0,
+            ..RetryPolicy::default()
+        };
+        let server_error = LlmError::Server {
+            status: 503,
+            body: String::new(),
+        };
+        assert_eq!(policy.delay_for(1, &server_error), Duration::from_secs(1));
+        assert_eq!(policy.delay_for(3, &server_error), Duration::from_secs(4));
+        assert_eq!(policy.delay_for(10, &server_error), Duration::from_secs(30));
+
+        let rate_limited = LlmError::RateLimited {
+            retry_after: Some(Duration::from_secs(7)),
+        };
+        assert_eq!(policy.delay_for(1, &rate_limited), Duration::from_secs(7));
+
+        // A proxy asking for a day does not stall the run
+        let rate_limited = LlmError::RateLimited {
+            retry_after: Some(Duration::from_secs(86_400)),
+        };
+        assert_eq!(policy.delay_for(1, &rate_limited), Duration::from_secs(30));
+
+        // Jitter does not push a capped backoff past the maximum
+        let jittered = RetryPolicy {
+            jitter: 1.0,
+            ..RetryPolicy::default()
+        };
+        for _ in 0..20 {
+            assert!(jittered.delay_for(10, &server_error) <= Duration::from_secs(30));
+        }
+    }
+
+    #[test]
+    fn test_retry_config_per_agent() {
+        let mut config: RetryConfig = toml::from_str(
+            r#"
+            max_attempts = 4
+            multiplier = 3.0
+
+            [agents.backend_developer]
+            max_attempts = 6
+            "#,
+        )
+        .expect("Invalid retry table");
+        config
+            .apply_env(
+                [
+                    ("LLM_RETRY_JITTER", "0"),
+                    ("LLM_RETRY_MAX_BACKOFF_MS_QA_TESTER", "500"),
+                    ("LLM_PROVIDER", "mock"),
+                ]
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .into_iter(),
+            )
+            .expect("Invalid retry variables");
+
+        let backend = config.for_agent("Backend Developer");
+        assert_eq!(backend.max_attempts, 6);
+        assert_eq!(backend.multiplier, 3.0);
+        assert_eq!(backend.jitter, 0.0);
+        assert_eq!(backend.max_backoff_ms, 30_000);
+
+        let qa = config.for_agent("QA Tester");
+        assert_eq!(qa.max_attempts, 4);
+        assert_eq!(qa.max_backoff_ms, 500);
+
+        let bad = [("LLM_RETRY_MAX_ATTEMPTS", "many")]
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .into_iter();
+        assert!(RetryConfig::default().apply_env(bad).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_with_retry_skips_permanent_errors() {
+        let calls = AtomicU32::new(0);
+        let res: Result<(), LlmError> = with_retry(
+            &instant_policy(5),
+            || async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err(LlmError::Client {
+                    status: 400,
+                    body: "bad request".to_string(),
+                })
+            },
+            |_, _| {},
+        )
+        .await;
+        assert!(res.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let calls = AtomicU32::new(0);
+        let res = with_retry(
+            &instant_policy(5),
+            || async {
+                match calls.fetch_add(1, Ordering::SeqCst) {
+                    0 | 1 => Err(LlmError::RateLimited { retry_after: None }),
+                    _ => Ok("done"),
+                }
+            },
+            |_, _| {},
+        )
+        .await;
+        assert_eq!(res.unwrap(), "done");
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,169 @@
+use crate::apis::llm_provider::LlmResult;
+use crate::models::general::error::LlmError;
+use crate::models::general::llm::{APIStreamChunk, ChatResponse, OllamaResponse, TokenUsage};
+
+// Callback receiving each piece of a streamed response
+pub type TokenSink<'a> = &'a (dyn Fn(&str) + Sync);
+
+// Splits a chunked response body into lines
+#[derive(Debug, Default)]
+pub struct LineBuffer {
+    pending: Vec<u8>,
+}
+
+impl LineBuffer {
+    // Complete lines of the body so far, a partial last line is kept for the next chunk
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.pending.extend_from_slice(chunk);
+
+        let mut lines = vec![];
+        while let Some(end) = self.pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            lines.push(String::from_utf8_lossy(&line).trim_end().to_string());
+        }
+        lines
+    }
+
+    // Whatever followed the last newline
+    pub fn finish(self) -> Option<String> {
+        let line = String::from_utf8_lossy(&self.pending).trim().to_string();
+        (!line.is_empty()).then_some(line)
+    }
+}
+
+// Builds the full response while forwarding each token
+pub struct StreamCollector<'a> {
+    content: String,
+    usage: Option<TokenUsage>,
+    on_token: TokenSink<'a>,
+}
+
+impl<'a> StreamCollector<'a> {
+    pub fn new(on_token: TokenSink<'a>) -> Self {
+        Self {
+            content: String::new(),
+            usage: None,
+            on_token,
+        }
+    }
+
+    fn token(&mut self, token: &str) {
+        if !token.is_empty() {
+            (self.on_token)(token);
+            self.content.push_str(token);
+        }
+    }
+
+    pub fn finish(self) -> ChatResponse {
+        ChatResponse {
+            content: self.content,
+            usage: self.usage,
+        }
+    }
+}
+
+// Line parser of a streaming API
+pub type StreamLineParser = fn(&str, &mut StreamCollector) -> LlmResult<()>;
+
+fn invalid_chunk(e: serde_json::Error) -> LlmError {
+    LlmError::InvalidResponse(format!("Invalid stream chunk: {}", e))
+}
+
+// OpenAI style server-sent events - `data: {chunk}` lines ending with `data: [DONE]`
+pub fn parse_sse_line(line: &str, collector: &mut StreamCollector) -> LlmResult<()> {
+    // Comments, keep-alives and other event fields carry no tokens
+    let Some(data) = line.strip_prefix("data:").map(str::trim) else {
+        return Ok(());
+    };
+    if data.is_empty() || data == "[DONE]" {
+        return Ok(());
+    }
+
+    let chunk: APIStreamChunk = serde_json::from_str(data).map_err(invalid_chunk)?;
+    for choice in chunk.choices {
+        if let Some(content) = choice.delta.content {
+            collector.token(&content);
+        }
+    }
+    if chunk.usage.is_some() {
+        collector.usage = chunk.usage;
+    }
+    Ok(())
+}
+
+// Ollama's newline delimited JSON - the last line carries the token counts
+pub fn parse_ndjson_line(line: &str, collector: &mut StreamCollector) -> LlmResult<()> {
+    if line.trim().is_empty() {
+        return Ok(());
+    }
+
+    let chunk: OllamaResponse = serde_json::from_str(line).map_err(invalid_chunk)?;
+    collector.token(&chunk.message.content);
+    if let (Some(prompt_tokens), Some(completion_tokens)) =
+        (chunk.prompt_eval_count, chunk.eval_count)
+    {
+        collector.usage = Some(TokenUsage {
+            prompt_tokens,
+            completion_tokens,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_parse_sse_stream_split_across_chunks() {
+        let body = concat!(
+            ": keep-alive\n",
+            "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"fn main\"}}]}\r\n\r\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"() {}\"}}]}\n\n",
+            "data: {\"choices\":[],\"usage\":{\"prompt_tokens\":12,\"completion_tokens\":4}}\n\n",
+            "data: [DONE]\n\n",
+        );
+
+        let tokens = Mutex::new(vec![]);
+        let on_token = |token: &str| tokens.lock().unwrap().push(token.to_string());
+        let mut collector = StreamCollector::new(&on_token);
+        let mut buffer = LineBuffer::default();
+
+        // Chunks do not line up with events
+        for chunk in body.as_bytes().chunks(7) {
+            for line in buffer.push(chunk) {
+                parse_sse_line(&line, &mut collector).expect("Invalid line");
+            }
+        }
+        assert_eq!(buffer.finish(), None);
+
+        let response = collector.finish();
+        assert_eq!(response.content, "fn main() {}");
+        assert_eq!(*tokens.lock().unwrap(), vec!["fn main", "() {}"]);
+        assert_eq!(
+            response.usage,
+            Some(TokenUsage {
+                prompt_tokens: 12,
+                completion_tokens: 4
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_ndjson_stream() {
+        let on_token = |_: &str| {};
+        let mut collector = StreamCollector::new(&on_token);
+        for line in [
+            "{\"message\":{\"content\":\"Hello\"},\"done\":false}",
+            "{\"message\":{\"content\":\" there\"},\"done\":true,\"prompt_eval_count\":3,\"eval_count\":2}",
+        ] {
+            parse_ndjson_line(line, &mut collector).expect("Invalid line");
+        }
+
+        let response = collector.finish();
+        assert_eq!(response.content, "Hello there");
+        assert_eq!(response.usage.map(|u| u.completion_tokens), Some(2));
+    }
+}
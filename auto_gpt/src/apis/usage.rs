@@ -0,0 +1,248 @@
+use crate::models::general::error::AutoGptError;
+use crate::models::general::llm::TokenUsage;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, OnceLock};
+
+// Price of a model in USD per 1000 tokens
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct ModelPrice {
+    pub prompt_per_1k: f64,
+    pub completion_per_1k: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct CostConfig {
+    // Keyed on the model name reported by the provider - unknown models cost nothing
+    pub prices: HashMap<String, ModelPrice>,
+    // The run is aborted before any call made once this much was spent
+    pub budget_usd: Option<f64>,
+}
+
+impl Default for CostConfig {
+    fn default() -> Self {
+        let prices = HashMap::from([
+            (
+                "gpt-4".to_string(),
+                ModelPrice {
+                    prompt_per_1k: 0.03,
+                    completion_per_1k: 0.06,
+                },
+            ),
+            (
+                "gpt-4o".to_string(),
+                ModelPrice {
+                    prompt_per_1k: 0.0025,
+                    completion_per_1k: 0.01,
+                },
+            ),
+        ]);
+        Self {
+            prices,
+            budget_usd: None,
+        }
+    }
+}
+
+impl CostConfig {
+    pub fn cost(&self, model: &str, usage: &TokenUsage) -> f64 {
+        match self.prices.get(model) {
+            Some(price) => {
+                (usage.prompt_tokens as f64 * price.prompt_per_1k
+                    + usage.completion_tokens as f64 * price.completion_per_1k)
+                    / 1000.0
+            }
+            None => 0.0,
+        }
+    }
+
+    // Fail once the spending reached the budget
+    pub fn check_budget(&self, spent: f64) -> Result<(), AutoGptError> {
+        match self.budget_usd {
+            Some(budget) if spent >= budget => Err(AutoGptError::BudgetExceeded { spent, budget }),
+            _ => Ok(()),
+        }
+    }
+}
+
+// One LLM call, attributed to the agent and `#[ai_function]` that made it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageRecord {
+    pub agent: String,
+    pub function: String,
+    pub model: String,
+    pub usage: TokenUsage,
+    // Provider did not report usage, the tokens are estimated from the text
+    pub estimated: bool,
+    pub cost_usd: f64,
+}
+
+// Totals for one agent and function
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct UsageLine {
+    pub agent: String,
+    pub function: String,
+    pub calls: u32,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cost_usd: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct UsageReport {
+    pub lines: Vec<UsageLine>,
+    pub total: UsageLine,
+}
+
+impl UsageReport {
+    // Totals per agent and function
+    pub fn from_records(records: &[UsageRecord]) -> Self {
+        let mut lines: BTreeMap<(String, String), UsageLine> = BTreeMap::new();
+        let mut total = UsageLine {
+            agent: "TOTAL".to_string(),
+            ..UsageLine::default()
+        };
+
+        for record in records {
+            let line = lines
+                .entry((record.agent.clone(), record.function.clone()))
+                .or_insert_with(|| UsageLine {
+                    agent: record.agent.clone(),
+                    function: record.function.clone(),
+                    ..UsageLine::default()
+                });
+            for line in [line, &mut total] {
+                line.calls += 1;
+                line.prompt_tokens += record.usage.prompt_tokens;
+                line.completion_tokens += record.usage.completion_tokens;
+                line.cost_usd += record.cost_usd;
+            }
+        }
+
+        Self {
+            lines: lines.into_values().collect(),
+            total,
+        }
+    }
+
+    pub fn print(&self) {
+        println!(
+            "{:<24} {:<32} {:>6} {:>10} {:>10} {:>10}",
+            "AGENT", "FUNCTION", "CALLS", "PROMPT", "COMPLETION", "COST USD"
+        );
+        for line in self.lines.iter().chain([&self.total]) {
+            println!(
+                "{:<24} {:<32} {:>6} {:>10} {:>10} {:>10.4}",
+                line.agent,
+                line.function,
+                line.calls,
+                line.prompt_tokens,
+                line.completion_tokens,
+                line.cost_usd
+            );
+        }
+    }
+}
+
+// Every LLM call of the run
+#[derive(Debug, Default)]
+pub struct UsageLedger {
+    records: Mutex<Vec<UsageRecord>>,
+}
+
+impl UsageLedger {
+    pub fn record(&self, record: UsageRecord) {
+        self.records.lock().unwrap().push(record);
+    }
+
+    // Carry over the calls of a resumed run
+    pub fn extend(&self, records: Vec<UsageRecord>) {
+        self.records.lock().unwrap().extend(records);
+    }
+
+    pub fn records(&self) -> Vec<UsageRecord> {
+        self.records.lock().unwrap().clone()
+    }
+
+    pub fn total_cost(&self) -> f64 {
+        self.records
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|r| r.cost_usd)
+            .sum()
+    }
+
+    pub fn report(&self) -> UsageReport {
+        UsageReport::from_records(&self.records.lock().unwrap())
+    }
+}
+
+static USAGE_LEDGER: OnceLock<Arc<UsageLedger>> = OnceLock::new();
+
+tokio::task_local! {
+    static SCOPED_USAGE_LEDGER: Arc<UsageLedger>;
+}
+
+// Ledger shared by all agents - a ledger set with `with_usage_ledger` takes precedence
+pub fn usage_ledger() -> Arc<UsageLedger> {
+    if let Ok(ledger) = SCOPED_USAGE_LEDGER.try_with(|ledger| ledger.clone()) {
+        return ledger;
+    }
+    USAGE_LEDGER.get_or_init(Arc::default).clone()
+}
+
+// Run a future with every LLM call accounted in the given ledger
+#[cfg(test)]
+pub async fn with_usage_ledger<F: std::future::Future>(
+    ledger: Arc<UsageLedger>,
+    f: F,
+) -> F::Output {
+    SCOPED_USAGE_LEDGER.scope(ledger, f).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(agent: &str, function: &str, prompt_tokens: u64) -> UsageRecord {
+        let usage = TokenUsage {
+            prompt_tokens,
+            completion_tokens: 500,
+        };
+        UsageRecord {
+            agent: agent.to_string(),
+            function: function.to_string(),
+            model: "gpt-4".to_string(),
+            usage,
+            estimated: false,
+            cost_usd: CostConfig::default().cost("gpt-4", &usage),
+        }
+    }
+
+    #[test]
+    fn test_usage_report_and_budget() {
+        let ledger = UsageLedger::default();
+        ledger.record(record("Backend Developer", "print_fixed_code", 1000));
+        ledger.record(record("Backend Developer", "print_fixed_code", 1000));
+        ledger.record(record("Solutions Architect", "print_project_scope", 1000));
+
+        let report = ledger.report();
+        assert_eq!(report.lines.len(), 2);
+        assert_eq!(report.lines[0].calls, 2);
+        assert_eq!(report.total.prompt_tokens, 3000);
+        // 3 x (1000 x 0.03 + 500 x 0.06) / 1000
+        assert!((report.total.cost_usd - 0.18).abs() < 1e-9);
+
+        let config = CostConfig {
+            budget_usd: Some(0.1),
+            ..CostConfig::default()
+        };
+        assert!(matches!(
+            config.check_budget(ledger.total_cost()),
+            Err(AutoGptError::BudgetExceeded { .. })
+        ));
+        assert!(CostConfig::default().check_budget(100.0).is_ok());
+    }
+}
@@ -2,7 +2,8 @@ use crossterm::{
     style::{Color, ResetColor, SetForegroundColor},
     ExecutableCommand,
 };
-use std::io::{stdin, stdout};
+use similar::{ChangeTag, TextDiff};
+use std::io::{stdin, stdout, Write};
 
 #[derive(PartialEq, Debug)]
 pub enum PrintCommand {
@@ -12,15 +13,19 @@ pub enum PrintCommand {
 }
 
 impl PrintCommand {
+    fn color(&self) -> Color {
+        match self {
+            Self::AICall => Color::Cyan,
+            Self::UnitTest => Color::Magenta,
+            Self::Issue => Color::Red,
+        }
+    }
+
     pub fn print_agent_message(&self, agent_pos: &str, agent_statement: &str) {
         let mut stout = stdout();
 
         // Decide the color based on self
-        let statement_color = match self {
-            Self::AICall => Color::Cyan,
-            Self::UnitTest => Color::Magenta,
-            Self::Issue => Color::Red,
-        };
+        let statement_color = self.color();
 
         stout.execute(SetForegroundColor(Color::Green)).unwrap();
         print!("Agent: {}: ", agent_pos);
@@ -32,14 +37,52 @@ impl PrintCommand {
         // Reset the color
         stout.execute(ResetColor).unwrap();
     }
+
+    // Print a piece of a streamed LLM response as soon as it arrives
+    pub fn print_stream_token(&self, token: &str) {
+        let mut stout = stdout();
+        stout.execute(SetForegroundColor(self.color())).unwrap();
+        print!("{}", token);
+        stout.execute(ResetColor).unwrap();
+        stout.flush().unwrap();
+    }
+
+    // End the line of a streamed response
+    pub fn print_stream_end(&self) {
+        println!();
+    }
 }
+// Print a coloured line diff between two versions of a file
+pub fn print_code_diff(old_name: &str, old: &str, new_name: &str, new: &str) {
+    let mut stout = stdout();
+    let diff = TextDiff::from_lines(old, new);
+
+    println!("--- {}\n+++ {}", old_name, new_name);
+    for hunk in diff.unified_diff().context_radius(3).iter_hunks() {
+        println!("{}", hunk.header());
+        for change in hunk.iter_changes() {
+            let (sign, color) = match change.tag() {
+                ChangeTag::Delete => ("-", Color::Red),
+                ChangeTag::Insert => ("+", Color::Green),
+                ChangeTag::Equal => (" ", Color::Reset),
+            };
+            stout.execute(SetForegroundColor(color)).unwrap();
+            print!("{}{}", sign, change);
+            if change.missing_newline() {
+                println!();
+            }
+        }
+    }
+    stout.execute(ResetColor).unwrap();
+}
+
 pub fn get_user_response(question: &str) -> String {
     let mut stout = stdout();
 
     // Print the question in specific color
     stout.execute(SetForegroundColor(Color::Blue)).unwrap();
 
-    println!("");
+    println!();
     println!("{}", question);
 
     // Reset the color
@@ -55,13 +98,22 @@ pub fn get_user_response(question: &str) -> String {
     user_response.trim().to_string()
 }
 
+// Decision of the user on generated code
+#[derive(Debug, PartialEq)]
+pub enum CodeReview {
+    Approve,
+    Reject,
+    RollBack(usize),
+}
+
 // Get user response that code is safe to execute
-pub fn confirm_safe_code() -> bool {
+// `iterations` are the earlier versions of the code that can be restored instead
+pub fn confirm_safe_code(iterations: &[usize]) -> CodeReview {
     let mut stdout = stdout();
     loop {
         // Print the question in specified color
         stdout.execute(SetForegroundColor(Color::Blue)).unwrap();
-        println!("");
+        println!();
         print!("WARNING: You are about to run code written entirely by AI. ");
         println!("Review your code and confirm you wish to continue.");
 
@@ -73,6 +125,10 @@ pub fn confirm_safe_code() -> bool {
         println!("[1] All good");
         stdout.execute(SetForegroundColor(Color::DarkRed)).unwrap();
         println!("[2] Lets stop this project");
+        if !iterations.is_empty() {
+            stdout.execute(SetForegroundColor(Color::Yellow)).unwrap();
+            println!("[3] Roll back to an earlier iteration");
+        }
 
         // Reset Color
         stdout.execute(ResetColor).unwrap();
@@ -88,10 +144,21 @@ pub fn confirm_safe_code() -> bool {
 
         // Match response
         match human_response.as_str() {
-            "1" | "ok" | "y" => return true,
-            "2" | "no" | "n" => return false,
+            "1" | "ok" | "y" => return CodeReview::Approve,
+            "2" | "no" | "n" => return CodeReview::Reject,
+            "3" | "r" if !iterations.is_empty() => {
+                let iteration = get_user_response(
+                    format!("Roll back to which iteration? {:?}", iterations).as_str(),
+                );
+                match iteration.parse::<usize>() {
+                    Ok(iteration) if iterations.contains(&iteration) => {
+                        return CodeReview::RollBack(iteration)
+                    }
+                    _ => println!("Invalid iteration: {}", iteration),
+                }
+            }
             _ => {
-                println!("Invalid input. Please select '1' or '2'")
+                println!("Invalid input. Please select '1', '2' or '3'")
             }
         }
     }
@@ -0,0 +1,457 @@
+use crate::apis::llm_provider::LlmConfig;
+use crate::apis::retry::RetryConfig;
+use crate::apis::usage::CostConfig;
+use crate::helpers::database_schema::{DatabaseConfig, DatabaseStore, INITIAL_MIGRATION};
+use crate::helpers::safety_scan::SafetyPolicy;
+use crate::helpers::sandbox::{SandboxConfig, SandboxMode};
+use crate::helpers::test_report::INTEGRATION_TESTS;
+use crate::models::agent_basic::agent_memory::{MemoryConfig, MemoryMode};
+use crate::models::agent_manager::agent_registry::DEFAULT_PIPELINE;
+use crate::models::general::error::{AutoGptError, LlmError};
+use clap::Args;
+use dotenv::dotenv;
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+// Config file read when neither --config nor AUTO_GPT_CONFIG is given
+const DEFAULT_CONFIG_FILE: &str = "auto_gpt.toml";
+
+// Where the agents read templates and write the generated project
+// Relative paths are resolved from the current directory
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct WorkspaceConfig {
+    pub code_template_path: PathBuf,
+    // Vetted authentication module copied into projects that need login
+    pub auth_module_path: PathBuf,
+    pub project_dir: PathBuf,
+    pub api_schema_path: PathBuf,
+    pub server_port: u16,
+    // Never prompt - generated code is run without asking for confirmation
+    pub non_interactive: bool,
+    // Print LLM responses token by token while they are generated
+    pub stream: bool,
+    // Agents run by the managing agent, in order
+    pub pipeline: Vec<String>,
+    // Each build checkpoints into a new directory under this one
+    pub runs_dir: PathBuf,
+    // How much of its earlier calls each agent sends to the LLM
+    pub memory: MemoryConfig,
+    // Model prices and the spending limit of a run
+    pub costs: CostConfig,
+    // How LLM calls are retried, for every agent or one of them
+    pub retry: RetryConfig,
+    // Isolation and limits of the generated server while it is built and tested
+    pub sandbox: SandboxConfig,
+    // What the safety scan of generated code rejects
+    pub safety: SafetyPolicy,
+    // How the generated server persists the entities of the database designer
+    pub database: DatabaseConfig,
+    // Falls back to the LLM_PROVIDER environment variables when not set
+    pub llm: Option<LlmConfig>,
+}
+
+impl Default for WorkspaceConfig {
+    fn default() -> Self {
+        Self {
+            code_template_path: PathBuf::from("../web_template/src/code_template.rs"),
+            auth_module_path: PathBuf::from("../web_template/src/auth.rs"),
+            project_dir: PathBuf::from("../web_template"),
+            api_schema_path: PathBuf::from("schemas/api_schema.json"),
+            server_port: 0,
+            non_interactive: false,
+            stream: false,
+            pipeline: DEFAULT_PIPELINE
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            runs_dir: PathBuf::from("runs"),
+            memory: MemoryConfig::default(),
+            costs: CostConfig::default(),
+            retry: RetryConfig::default(),
+            sandbox: SandboxConfig::default(),
+            safety: SafetyPolicy::default(),
+            database: DatabaseConfig::default(),
+            llm: None,
+        }
+    }
+}
+
+// Command line flags - take precedence over the config file and environment
+#[derive(Debug, Clone, Default, Args)]
+pub struct ConfigArgs {
+    /// TOML config file, defaults to $AUTO_GPT_CONFIG or ./auto_gpt.toml
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Code template given to the backend agent
+    #[arg(long, global = true)]
+    pub code_template: Option<PathBuf>,
+
+    /// Directory of the generated web server project
+    #[arg(long, global = true)]
+    pub output_dir: Option<PathBuf>,
+
+    /// Where the extracted API schema is written
+    #[arg(long, global = true)]
+    pub api_schema: Option<PathBuf>,
+
+    /// Port the generated server listens on
+    #[arg(long, global = true)]
+    pub port: Option<u16>,
+
+    /// LLM provider: azure, openai, ollama or mock
+    #[arg(long, global = true)]
+    pub provider: Option<String>,
+
+    /// Run generated code without asking for confirmation
+    #[arg(long, global = true)]
+    pub non_interactive: bool,
+
+    /// Print LLM responses live while they are generated
+    #[arg(long, global = true)]
+    pub stream: bool,
+
+    /// Agents to run, in order, e.g. architect,database,backend,qa,frontend
+    #[arg(long, global = true, value_delimiter = ',')]
+    pub pipeline: Option<Vec<String>>,
+
+    /// Abort the run once this many USD were spent on LLM calls
+    #[arg(long, global = true)]
+    pub budget_usd: Option<f64>,
+
+    /// Directory the run checkpoints are written to
+    #[arg(long, global = true)]
+    pub runs_dir: Option<PathBuf>,
+}
+
+fn config_error(path: &Path, e: impl std::fmt::Display) -> AutoGptError {
+    AutoGptError::Config(format!("{}: {}", path.display(), e))
+}
+
+impl WorkspaceConfig {
+    // Parse a TOML config file
+    pub fn from_file(path: &Path) -> Result<Self, AutoGptError> {
+        let contents =
+            fs::read_to_string(path).map_err(AutoGptError::io(path.display().to_string()))?;
+        toml::from_str(&contents).map_err(|e| config_error(path, e))
+    }
+
+    // Defaults, then the config file, then AUTO_GPT_* environment variables, then flags
+    pub fn load(args: &ConfigArgs) -> Result<Self, AutoGptError> {
+        dotenv().ok();
+
+        let config_path = args
+            .config
+            .clone()
+            .or_else(|| env::var("AUTO_GPT_CONFIG").ok().map(PathBuf::from));
+        let mut config = match config_path {
+            Some(path) => Self::from_file(&path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                Self::from_file(Path::new(DEFAULT_CONFIG_FILE))?
+            }
+            None => Self::default(),
+        };
+
+        config.apply_env()?;
+        config.apply_args(args)?;
+        Ok(config)
+    }
+
+    fn apply_env(&mut self) -> Result<(), AutoGptError> {
+        if let Ok(path) = env::var("AUTO_GPT_CODE_TEMPLATE") {
+            self.code_template_path = PathBuf::from(path);
+        }
+        if let Ok(path) = env::var("AUTO_GPT_OUTPUT_DIR") {
+            self.project_dir = PathBuf::from(path);
+        }
+        if let Ok(path) = env::var("AUTO_GPT_API_SCHEMA") {
+            self.api_schema_path = PathBuf::from(path);
+        }
+        if let Ok(port) = env::var("AUTO_GPT_PORT") {
+            self.server_port = port
+                .parse()
+                .map_err(|e| AutoGptError::Config(format!("AUTO_GPT_PORT: {}", e)))?;
+        }
+        if let Ok(non_interactive) = env::var("AUTO_GPT_NON_INTERACTIVE") {
+            self.non_interactive = matches!(non_interactive.as_str(), "1" | "true");
+        }
+        if let Ok(stream) = env::var("AUTO_GPT_STREAM") {
+            self.stream = matches!(stream.as_str(), "1" | "true");
+        }
+        if let Ok(pipeline) = env::var("AUTO_GPT_PIPELINE") {
+            self.pipeline = pipeline
+                .split(',')
+                .map(|name| name.trim().to_string())
+                .collect();
+        }
+        if let Ok(path) = env::var("AUTO_GPT_RUNS_DIR") {
+            self.runs_dir = PathBuf::from(path);
+        }
+        if let Ok(mode) = env::var("AUTO_GPT_MEMORY") {
+            self.memory.mode = match mode.to_lowercase().as_str() {
+                "off" => MemoryMode::Off,
+                "window" => MemoryMode::Window,
+                "summary" => MemoryMode::Summary,
+                other => {
+                    return Err(AutoGptError::Config(format!(
+                        "AUTO_GPT_MEMORY: unknown mode {}",
+                        other
+                    )))
+                }
+            };
+        }
+        if let Ok(mode) = env::var("AUTO_GPT_SANDBOX") {
+            self.sandbox.mode = match mode.to_lowercase().as_str() {
+                "bubblewrap" => SandboxMode::Bubblewrap,
+                "host" => SandboxMode::Host,
+                other => {
+                    return Err(AutoGptError::Config(format!(
+                        "AUTO_GPT_SANDBOX: unknown mode {}",
+                        other
+                    )))
+                }
+            };
+        }
+        if let Ok(store) = env::var("AUTO_GPT_DATABASE_STORE") {
+            self.database.store = match store.to_lowercase().as_str() {
+                "sqlite" => DatabaseStore::Sqlite,
+                "json" => DatabaseStore::Json,
+                other => {
+                    return Err(AutoGptError::Config(format!(
+                        "AUTO_GPT_DATABASE_STORE: unknown store {}",
+                        other
+                    )))
+                }
+            };
+        }
+        if let Ok(budget) = env::var("AUTO_GPT_BUDGET_USD") {
+            let budget = budget
+                .parse()
+                .map_err(|e| AutoGptError::Config(format!("AUTO_GPT_BUDGET_USD: {}", e)))?;
+            self.costs.budget_usd = Some(budget);
+        }
+        if let Ok(budget) = env::var("AUTO_GPT_MEMORY_TOKEN_BUDGET") {
+            self.memory.token_budget = budget.parse().map_err(|e| {
+                AutoGptError::Config(format!("AUTO_GPT_MEMORY_TOKEN_BUDGET: {}", e))
+            })?;
+        }
+        self.retry.apply_env(env::vars())?;
+        Ok(())
+    }
+
+    fn apply_args(&mut self, args: &ConfigArgs) -> Result<(), AutoGptError> {
+        if let Some(path) = &args.code_template {
+            self.code_template_path = path.clone();
+        }
+        if let Some(path) = &args.output_dir {
+            self.project_dir = path.clone();
+        }
+        if let Some(path) = &args.api_schema {
+            self.api_schema_path = path.clone();
+        }
+        if let Some(port) = args.port {
+            self.server_port = port;
+        }
+        if let Some(provider) = &args.provider {
+            let llm = LlmConfig::for_provider(provider)
+                .map_err(|e| AutoGptError::Config(e.to_string()))?;
+            self.llm = Some(llm);
+        }
+        if args.non_interactive {
+            self.non_interactive = true;
+        }
+        if args.stream {
+            self.stream = true;
+        }
+        if let Some(pipeline) = &args.pipeline {
+            self.pipeline = pipeline.clone();
+        }
+        if let Some(budget) = args.budget_usd {
+            self.costs.budget_usd = Some(budget);
+        }
+        if let Some(path) = &args.runs_dir {
+            self.runs_dir = path.clone();
+        }
+        Ok(())
+    }
+
+    // main.rs of the generated project
+    pub fn exec_main_path(&self) -> PathBuf {
+        self.project_dir.join("src").join("main.rs")
+    }
+
+    // Where the authentication module goes in the generated project, next to main.rs
+    pub fn project_auth_module_path(&self) -> PathBuf {
+        self.project_dir.join("src").join("auth.rs")
+    }
+
+    // Static frontend of the generated project, served from its `static` directory
+    pub fn frontend_dir(&self) -> PathBuf {
+        self.project_dir.join("static")
+    }
+
+    // Integration tests of the generated project, written by the QA tester
+    pub fn integration_tests_path(&self) -> PathBuf {
+        self.project_dir
+            .join("tests")
+            .join(format!("{}.rs", INTEGRATION_TESTS))
+    }
+
+    // Entities of the database designer, next to the endpoint schema
+    pub fn database_schema_path(&self) -> PathBuf {
+        self.api_schema_path.with_file_name("database_schema.json")
+    }
+
+    // Creates the tables of the database designer's schema
+    pub fn migration_path(&self) -> PathBuf {
+        self.project_dir.join(INITIAL_MIGRATION)
+    }
+
+    // Manifest of the generated project
+    pub fn project_manifest_path(&self) -> PathBuf {
+        self.project_dir.join("Cargo.toml")
+    }
+
+    // OpenAPI version of the endpoint schema, next to it
+    pub fn openapi_path(&self) -> PathBuf {
+        self.api_schema_path.with_file_name("openapi.json")
+    }
+
+    // LLM settings from the config file, or from the environment
+    pub fn llm_config(&self) -> Result<LlmConfig, LlmError> {
+        match &self.llm {
+            Some(llm) => Ok(llm.clone()),
+            None => LlmConfig::from_env(),
+        }
+    }
+}
+
+static WORKSPACE_CONFIG: OnceLock<WorkspaceConfig> = OnceLock::new();
+
+tokio::task_local! {
+    static SCOPED_WORKSPACE_CONFIG: WorkspaceConfig;
+}
+
+// Use this config for the rest of the process - call once at start up
+pub fn set_workspace_config(config: WorkspaceConfig) -> Result<(), AutoGptError> {
+    WORKSPACE_CONFIG
+        .set(config)
+        .map_err(|_| AutoGptError::Config("Workspace config is already set".to_string()))
+}
+
+// Config used by the helpers - a config set with `with_workspace_config` takes precedence
+// Loaded from file and environment on first use if nothing was set
+pub fn workspace_config() -> Result<WorkspaceConfig, AutoGptError> {
+    if let Ok(config) = SCOPED_WORKSPACE_CONFIG.try_with(|config| config.clone()) {
+        return Ok(config);
+    }
+    if let Some(config) = WORKSPACE_CONFIG.get() {
+        return Ok(config.clone());
+    }
+    let config = WorkspaceConfig::load(&ConfigArgs::default())?;
+    Ok(WORKSPACE_CONFIG.get_or_init(|| config).clone())
+}
+
+// Run a future with the helpers reading from the given config
+#[cfg(test)]
+pub async fn with_workspace_config<F: std::future::Future>(
+    config: WorkspaceConfig,
+    f: F,
+) -> F::Output {
+    SCOPED_WORKSPACE_CONFIG.scope(config, f).await
+}
+
+// Dependency free server for the scratch project, binds the port it is given and serves nothing
+#[cfg(test)]
+pub const SCRATCH_SERVER: &str = r#"fn main() {
+    let port: u16 = std::env::var("PORT").unwrap().parse().unwrap();
+    let listener = std::net::TcpListener::bind(("127.0.0.1", port)).unwrap();
+    for _ in listener.incoming() {}
+}"#;
+
+// Throwaway project that builds offline, for tests that run the backend agent
+#[cfg(test)]
+pub fn scratch_workspace() -> (tempfile::TempDir, WorkspaceConfig) {
+    let dir = tempfile::tempdir().expect("Failed to create scratch project");
+    let root = dir.path();
+    fs::create_dir_all(root.join("src")).unwrap();
+    fs::write(
+        root.join("Cargo.toml"),
+        "[package]\nname = \"scratch_server\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[workspace]\n",
+    )
+    .unwrap();
+    fs::write(root.join("src/main.rs"), SCRATCH_SERVER).unwrap();
+    fs::write(root.join("code_template.rs"), "fn main() {}\n").unwrap();
+    fs::write(root.join("auth.rs"), "pub fn hash_password() {}\n").unwrap();
+
+    let config = WorkspaceConfig {
+        code_template_path: root.join("code_template.rs"),
+        auth_module_path: root.join("auth.rs"),
+        project_dir: root.to_path_buf(),
+        api_schema_path: root.join("schemas/api_schema.json"),
+        non_interactive: true,
+        // Tests run where bubblewrap may not be installed
+        sandbox: SandboxConfig {
+            mode: SandboxMode::Host,
+            target_dir: root.join("sandbox_target"),
+            ..SandboxConfig::default()
+        },
+        // No dependencies to download
+        database: DatabaseConfig {
+            store: DatabaseStore::Json,
+        },
+        ..WorkspaceConfig::default()
+    };
+    (dir, config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_from_toml_and_args() {
+        let toml_str = r#"
+            project_dir = "/tmp/generated"
+            server_port = 9000
+
+            [llm]
+            provider = "ollama"
+            base_url = "http://localhost:11434"
+            model = "llama3"
+        "#;
+        let mut config: WorkspaceConfig = toml::from_str(toml_str).expect("Invalid config");
+        assert_eq!(
+            config.exec_main_path(),
+            PathBuf::from("/tmp/generated/src/main.rs")
+        );
+        assert_eq!(
+            config.code_template_path,
+            WorkspaceConfig::default().code_template_path
+        );
+        assert!(matches!(config.llm_config(), Ok(LlmConfig::Ollama { .. })));
+
+        config
+            .apply_args(&ConfigArgs {
+                port: Some(9100),
+                provider: Some("openai".to_string()),
+                ..ConfigArgs::default()
+            })
+            .expect("Invalid args");
+        assert_eq!(config.server_port, 9100);
+        assert!(matches!(config.llm, Some(LlmConfig::OpenAI { .. })));
+        assert_eq!(config.project_dir, PathBuf::from("/tmp/generated"));
+    }
+
+    #[test]
+    fn test_example_config_is_valid() {
+        let example: WorkspaceConfig =
+            toml::from_str(include_str!("../../auto_gpt.example.toml")).expect("Invalid example");
+        assert_eq!(example.server_port, 0);
+        assert_eq!(example.retry.for_agent("Backend Developer").max_attempts, 5);
+    }
+}
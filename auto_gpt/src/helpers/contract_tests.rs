@@ -0,0 +1,696 @@
+use crate::models::agents::agent_traits::RouteObject;
+use reqwest::{Client, Method};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::fmt;
+
+// Id used for a dynamic segment when no created resource has a matching field
+const DEFAULT_SEGMENT: &str = "1";
+
+// Routes of the authentication module that work without a session
+pub const PUBLIC_AUTH_ROUTES: [&str; 2] = ["/register", "/login"];
+
+// Revokes the session, so it is called after every other logged in test
+pub const LOGOUT_ROUTE: &str = "/logout";
+
+// User the contract tests register and log in as
+const TEST_USERNAME: &str = "contract_test";
+const TEST_PASSWORD: &str = "contract-test-password";
+
+// "None", "not_provided" and the like mean the schema does not say
+pub fn is_unspecified(shape: &Value) -> bool {
+    match shape {
+        Value::Null => true,
+        Value::String(s) => matches!(
+            s.to_lowercase().as_str(),
+            "" | "none" | "null" | "not_provided" | "any" | "unknown"
+        ),
+        _ => false,
+    }
+}
+
+pub fn type_name(shape: &str) -> String {
+    shape.to_lowercase().replace(' ', "")
+}
+
+// Example value for a schema such as `{"id": "number", "name": "string"}`
+pub fn synthesize(shape: &Value) -> Option<Value> {
+    if is_unspecified(shape) {
+        return None;
+    }
+    let value = match shape {
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, field)| (key.clone(), synthesize(field).unwrap_or(Value::Null)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().filter_map(synthesize).collect()),
+        Value::String(name) => {
+            let name = type_name(name);
+            if name.starts_with("option") || name.contains("null") {
+                Value::Null
+            } else if name.contains("bool") {
+                json!(true)
+            } else if name.contains("float") || is_float_type(&name) {
+                json!(1.5)
+            } else if name.contains("number") || name.contains("int") || is_integer_type(&name) {
+                json!(1)
+            } else if name.contains("vec") || name.contains("array") || name.contains("list") {
+                json!([])
+            } else {
+                json!("test")
+            }
+        }
+        literal => literal.clone(),
+    };
+    Some(value)
+}
+
+pub fn is_float_type(name: &str) -> bool {
+    matches!(name, "f32" | "f64")
+}
+
+pub fn is_integer_type(name: &str) -> bool {
+    matches!(
+        name,
+        "u8" | "u16"
+            | "u32"
+            | "u64"
+            | "u128"
+            | "usize"
+            | "i8"
+            | "i16"
+            | "i32"
+            | "i64"
+            | "i128"
+            | "isize"
+    )
+}
+
+// Check a response against its schema, the error says where it differs
+pub fn check_shape(shape: &Value, actual: &Value) -> Result<(), String> {
+    check_shape_at("response", shape, actual)
+}
+
+fn check_shape_at(path: &str, shape: &Value, actual: &Value) -> Result<(), String> {
+    if is_unspecified(shape) {
+        return Ok(());
+    }
+    match (shape, actual) {
+        (Value::Object(fields), Value::Object(values)) => {
+            for (key, field) in fields {
+                let path = format!("{}.{}", path, key);
+                match values.get(key) {
+                    Some(value) => check_shape_at(&path, field, value)?,
+                    None if is_optional(field) => {}
+                    None => return Err(format!("{} is missing", path)),
+                }
+            }
+            Ok(())
+        }
+        // A list endpoint is often described by its item
+        (Value::Object(_), Value::Array(items)) => items
+            .iter()
+            .enumerate()
+            .try_for_each(|(i, item)| check_shape_at(&format!("{}[{}]", path, i), shape, item)),
+        (Value::Array(item_shapes), Value::Array(items)) => match item_shapes.first() {
+            Some(item_shape) => items.iter().enumerate().try_for_each(|(i, item)| {
+                check_shape_at(&format!("{}[{}]", path, i), item_shape, item)
+            }),
+            None => Ok(()),
+        },
+        (Value::String(name), actual) => check_type(path, &type_name(name), actual),
+        (expected, actual) => Err(format!("{} should be {} but is {}", path, expected, actual)),
+    }
+}
+
+fn is_optional(shape: &Value) -> bool {
+    matches!(shape, Value::String(name) if type_name(name).starts_with("option"))
+}
+
+fn check_type(path: &str, name: &str, actual: &Value) -> Result<(), String> {
+    let matches = match actual {
+        Value::Null => name.starts_with("option") || name.contains("null"),
+        Value::Bool(_) => name.contains("bool"),
+        Value::Number(_) => {
+            name.contains("number")
+                || name.contains("int")
+                || name.contains("float")
+                || is_integer_type(name)
+                || is_float_type(name)
+        }
+        Value::String(_) => {
+            name.contains("string") || name.contains("str") || name.contains("date")
+        }
+        Value::Array(_) => name.contains("vec") || name.contains("array") || name.contains("list"),
+        Value::Object(_) => name.contains("object") || name.contains("map"),
+    };
+
+    // Type names the schema invents are not checked
+    let known = [
+        "bool", "number", "int", "float", "string", "str", "vec", "array", "list", "object",
+    ]
+    .iter()
+    .any(|known| name.contains(known))
+        || is_integer_type(name)
+        || is_float_type(name);
+    match matches || !known {
+        true => Ok(()),
+        false => Err(format!("{} should be {} but is {}", path, name, actual)),
+    }
+}
+
+// Result of calling one endpoint
+#[derive(Debug, Clone, PartialEq)]
+pub struct EndpointResult {
+    pub method: String,
+    pub route: String,
+    // Route with its dynamic segments filled in
+    pub url_path: String,
+    pub status: Option<u16>,
+    pub failure: Option<String>,
+}
+
+impl fmt::Display for EndpointResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.method, self.url_path)?;
+        if let Some(status) = self.status {
+            write!(f, " -> {}", status)?;
+        }
+        match &self.failure {
+            Some(failure) => write!(f, ": FAILED, {}", failure),
+            None => write!(f, ": ok"),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ContractReport {
+    pub results: Vec<EndpointResult>,
+}
+
+impl ContractReport {
+    pub fn failures(&self) -> Vec<&EndpointResult> {
+        self.results
+            .iter()
+            .filter(|r| r.failure.is_some())
+            .collect()
+    }
+}
+
+impl fmt::Display for ContractReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for result in &self.results {
+            writeln!(f, "{}", result)?;
+        }
+        Ok(())
+    }
+}
+
+// Creates first so there is something to read, update and delete, deletes last
+fn method_order(method: &str) -> u8 {
+    match method {
+        "POST" => 0,
+        "GET" => 1,
+        "PUT" | "PATCH" => 2,
+        "DELETE" => 3,
+        _ => 4,
+    }
+}
+
+// Part of a route before its first dynamic segment, e.g. `/item` of `/item/{id}`
+fn static_prefix(route: &str) -> &str {
+    let end = route.find('{').unwrap_or(route.len());
+    route[..end].trim_end_matches('/')
+}
+
+// Fill `{name}` segments from what the closest matching POST created
+fn fill_route(route: &str, created: &HashMap<String, Map<String, Value>>) -> String {
+    let prefix = static_prefix(route);
+    let resource = created
+        .iter()
+        .filter(|(created_route, _)| prefix.starts_with(created_route.as_str()))
+        .max_by_key(|(created_route, _)| created_route.len())
+        .map(|(_, fields)| fields);
+
+    let mut filled = String::new();
+    let mut rest = route;
+    while let (Some(open), Some(close)) = (rest.find('{'), rest.find('}')) {
+        if close < open {
+            break;
+        }
+        let name = &rest[open + 1..close];
+        let value = resource
+            .and_then(|fields| fields.get(name))
+            .or_else(|| created.values().find_map(|fields| fields.get(name)))
+            .map(|value| match value {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            })
+            .unwrap_or_else(|| DEFAULT_SEGMENT.to_string());
+        filled.push_str(&rest[..open]);
+        filled.push_str(&value);
+        rest = &rest[close + 1..];
+    }
+    filled.push_str(rest);
+    filled
+}
+
+// Call every endpoint of the schema against a running server
+pub async fn run_contract_tests(
+    client: &Client,
+    base_url: &str,
+    endpoints: &[RouteObject],
+) -> ContractReport {
+    let mut endpoints: Vec<&RouteObject> = endpoints.iter().collect();
+    endpoints.sort_by_key(|endpoint| method_order(&endpoint.method.to_uppercase()));
+
+    let mut report = ContractReport::default();
+    let mut created: HashMap<String, Map<String, Value>> = HashMap::new();
+    for endpoint in endpoints {
+        let method_name = endpoint.method.to_uppercase();
+        let url_path = fill_route(&endpoint.route, &created);
+        let mut result = EndpointResult {
+            method: method_name.clone(),
+            route: endpoint.route.clone(),
+            url_path: url_path.clone(),
+            status: None,
+            failure: None,
+        };
+
+        let Ok(method) = Method::from_bytes(method_name.as_bytes()) else {
+            result.failure = Some("unknown HTTP method".to_string());
+            report.results.push(result);
+            continue;
+        };
+        let body = synthesize(&endpoint.request_body);
+        let mut request = client.request(method, format!("{}{}", base_url, url_path));
+        if let Some(body) = &body {
+            request = request.json(body);
+        }
+
+        match request.send().await {
+            Err(e) => result.failure = Some(format!("request failed, {}", e)),
+            Ok(response) => {
+                let status = response.status();
+                result.status = Some(status.as_u16());
+                let text = response.text().await.unwrap_or_default();
+                let json: Option<Value> = serde_json::from_str(&text).ok();
+
+                if !status.is_success() {
+                    result.failure = Some(format!("expected a 2xx status, body: {}", text));
+                } else if !is_unspecified(&endpoint.response) {
+                    result.failure = match &json {
+                        Some(json) => check_shape(&endpoint.response, json).err(),
+                        None => Some(format!("response is not JSON: {}", text)),
+                    };
+                }
+
+                // Remember what was created, the server's answer wins over what was sent
+                if method_name == "POST" && endpoint.route.find('{').is_none() {
+                    let mut fields = match body {
+                        Some(Value::Object(fields)) => fields,
+                        _ => Map::new(),
+                    };
+                    if let Some(Value::Object(returned)) = json {
+                        fields.extend(returned);
+                    }
+                    created.insert(static_prefix(&endpoint.route).to_string(), fields);
+                }
+            }
+        }
+        report.results.push(result);
+    }
+    report
+}
+
+pub fn is_public_route(route: &str) -> bool {
+    PUBLIC_AUTH_ROUTES.contains(&route.trim_end_matches('/'))
+}
+
+// Routes that start or end the session, not tested with the others
+pub fn is_session_route(route: &str) -> bool {
+    is_public_route(route) || route.trim_end_matches('/') == LOGOUT_ROUTE
+}
+
+// Send one request, the status and body of the response
+async fn send(
+    client: &Client,
+    method: &str,
+    url: &str,
+    body: Option<&Value>,
+) -> Result<(u16, String), String> {
+    let method = Method::from_bytes(method.as_bytes()).map_err(|_| "unknown HTTP method")?;
+    let mut request = client.request(method, url);
+    if let Some(body) = body {
+        request = request.json(body);
+    }
+    let response = request
+        .send()
+        .await
+        .map_err(|e| format!("request failed, {}", e))?;
+    let status = response.status().as_u16();
+    Ok((status, response.text().await.unwrap_or_default()))
+}
+
+// Register the test user and log in, the session token if that worked
+async fn log_in(
+    client: &Client,
+    base_url: &str,
+    endpoints: &[RouteObject],
+    report: &mut ContractReport,
+) -> Option<String> {
+    let mut token = None;
+    for route in PUBLIC_AUTH_ROUTES {
+        let mut result = EndpointResult {
+            method: "POST".to_string(),
+            route: route.to_string(),
+            url_path: route.to_string(),
+            status: None,
+            failure: None,
+        };
+        let Some(endpoint) = endpoints.iter().find(|endpoint| {
+            endpoint.method.eq_ignore_ascii_case("post") && endpoint.route == route
+        }) else {
+            result.failure = Some("missing, login is required".to_string());
+            report.results.push(result);
+            return None;
+        };
+
+        let mut body = match synthesize(&endpoint.request_body) {
+            Some(Value::Object(fields)) => fields,
+            _ => Map::new(),
+        };
+        body.insert("username".to_string(), json!(TEST_USERNAME));
+        body.insert("password".to_string(), json!(TEST_PASSWORD));
+
+        let url = format!("{}{}", base_url, route);
+        match send(client, "POST", &url, Some(&Value::Object(body))).await {
+            Err(failure) => result.failure = Some(failure),
+            Ok((status, text)) => {
+                result.status = Some(status);
+                if !(200..300).contains(&status) {
+                    result.failure = Some(format!("expected a 2xx status, body: {}", text));
+                } else if route == "/login" {
+                    token = serde_json::from_str::<Value>(&text)
+                        .ok()
+                        .and_then(|json| json.get("token")?.as_str().map(String::from));
+                    if token.is_none() {
+                        result.failure = Some(format!("response has no \"token\": {}", text));
+                    }
+                }
+            }
+        }
+        let failed = result.failure.is_some();
+        report.results.push(result);
+        if failed {
+            return None;
+        }
+    }
+    token
+}
+
+// Protected routes must reject anonymous requests, then the test user logs in for the other tests
+pub async fn run_auth_checks(
+    client: &Client,
+    base_url: &str,
+    endpoints: &[RouteObject],
+) -> (ContractReport, Option<String>) {
+    let mut report = ContractReport::default();
+    for endpoint in endpoints.iter().filter(|e| !is_public_route(&e.route)) {
+        let result = expect_rejected(client, base_url, endpoint, "without a session").await;
+        report.results.push(result);
+    }
+
+    let token = log_in(client, base_url, endpoints, &mut report).await;
+    (report, token)
+}
+
+// Call an endpoint that must answer 401 or 403
+async fn expect_rejected(
+    client: &Client,
+    base_url: &str,
+    endpoint: &RouteObject,
+    when: &str,
+) -> EndpointResult {
+    let method = endpoint.method.to_uppercase();
+    let url_path = fill_route(&endpoint.route, &HashMap::new());
+    let mut result = EndpointResult {
+        method: method.clone(),
+        route: endpoint.route.clone(),
+        url_path: url_path.clone(),
+        status: None,
+        failure: None,
+    };
+
+    let body = synthesize(&endpoint.request_body);
+    let url = format!("{}{}", base_url, url_path);
+    match send(client, &method, &url, body.as_ref()).await {
+        Err(failure) => result.failure = Some(failure),
+        Ok((status, _)) => {
+            result.status = Some(status);
+            if !matches!(status, 401 | 403) {
+                result.failure = Some(format!("expected 401 or 403 {}", when));
+            }
+        }
+    }
+    result
+}
+
+// Log out with the client's session, which protected routes must reject afterwards
+pub async fn run_logout_check(
+    client: &Client,
+    base_url: &str,
+    endpoints: &[RouteObject],
+) -> ContractReport {
+    let mut report = ContractReport::default();
+    let Some(logout) = endpoints
+        .iter()
+        .find(|endpoint| endpoint.route.trim_end_matches('/') == LOGOUT_ROUTE)
+    else {
+        return report;
+    };
+
+    let method = logout.method.to_uppercase();
+    let mut result = EndpointResult {
+        method: method.clone(),
+        route: logout.route.clone(),
+        url_path: logout.route.clone(),
+        status: None,
+        failure: None,
+    };
+    let body = synthesize(&logout.request_body);
+    let url = format!("{}{}", base_url, logout.route);
+    match send(client, &method, &url, body.as_ref()).await {
+        Err(failure) => result.failure = Some(failure),
+        Ok((status, text)) => {
+            result.status = Some(status);
+            if !(200..300).contains(&status) {
+                result.failure = Some(format!("expected a 2xx status, body: {}", text));
+            }
+        }
+    }
+    let logged_out = result.failure.is_none();
+    report.results.push(result);
+
+    let protected = endpoints.iter().find(|e| !is_session_route(&e.route));
+    if let (true, Some(endpoint)) = (logged_out, protected) {
+        let result = expect_rejected(client, base_url, endpoint, "after logout").await;
+        report.results.push(result);
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_synthesize_and_check_shape() {
+        let shape = json!({"id": "number", "name": "string", "completed": "bool", "note": "Option<String>"});
+        assert_eq!(
+            synthesize(&shape),
+            Some(json!({"id": 1, "name": "test", "completed": true, "note": null}))
+        );
+        assert_eq!(synthesize(&json!("None")), None);
+
+        assert!(check_shape(&shape, &json!({"id": 3, "name": "a", "completed": false})).is_ok());
+        assert!(check_shape(&shape, &json!([{"id": 3, "name": "a", "completed": false}])).is_ok());
+        assert_eq!(
+            check_shape(&shape, &json!({"id": "3", "name": "a", "completed": false})),
+            Err("response.id should be number but is \"3\"".to_string())
+        );
+        assert_eq!(
+            check_shape(&shape, &json!({"id": 3, "name": "a"})),
+            Err("response.completed is missing".to_string())
+        );
+        assert!(check_shape(&json!("not_provided"), &json!("anything")).is_ok());
+    }
+
+    // Fake server on a free port, `respond` gets the request line and the whole request
+    async fn serve<F>(respond: F) -> String
+    where
+        F: Fn(&str, &str) -> (&'static str, &'static str) + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = vec![0; 4096];
+                let n = stream.read(&mut buf).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..n]).to_string();
+                let line = request.lines().next().unwrap_or_default();
+
+                let (status, body) = respond(line, &request);
+                let response = format!(
+                    "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        base_url
+    }
+
+    #[tokio::test]
+    async fn test_contract_tests_fill_dynamic_routes() {
+        // Created items get id 42
+        let base_url = serve(|line, _| match line {
+            l if l.starts_with("POST /item ") => ("201 Created", r#"{"id":42}"#),
+            l if l.starts_with("GET /item/42 ") => {
+                ("200 OK", r#"{"id":42,"name":"test","completed":true}"#)
+            }
+            l if l.starts_with("DELETE /item/42 ") => ("200 OK", ""),
+            _ => ("404 Not Found", ""),
+        })
+        .await;
+
+        let item = json!({"id": "number", "name": "string", "completed": "bool"});
+        let route = |method: &str, route: &str, request_body: Value, response: Value| RouteObject {
+            is_route_dynamic: route.contains('{'),
+            method: method.to_string(),
+            request_body,
+            response,
+            route: route.to_string(),
+        };
+        let endpoints = vec![
+            route("delete", "/item/{id}", json!("None"), json!("None")),
+            route("get", "/item/{id}", json!("None"), item.clone()),
+            route("post", "/item", item, json!("None")),
+            route("get", "/missing", json!("None"), json!("None")),
+        ];
+
+        let report = run_contract_tests(&Client::new(), &base_url, &endpoints).await;
+        let paths: Vec<&str> = report.results.iter().map(|r| r.url_path.as_str()).collect();
+        assert_eq!(paths, vec!["/item", "/item/42", "/missing", "/item/42"]);
+
+        let failures = report.failures();
+        assert_eq!(failures.len(), 1, "{}", report);
+        assert_eq!(failures[0].route, "/missing");
+        assert_eq!(failures[0].status, Some(404));
+    }
+
+    #[tokio::test]
+    async fn test_auth_checks_reject_anonymous_requests() {
+        // Only `/task` is protected, and `/open` forgot to be
+        let base_url = serve(|line, request| {
+            let has_session = request.contains("Bearer secret");
+            match line {
+                l if l.starts_with("POST /register ") => ("200 OK", r#"{"id":1}"#),
+                l if l.starts_with("POST /login ") && request.contains(TEST_PASSWORD) => {
+                    ("200 OK", r#"{"token":"secret"}"#)
+                }
+                l if l.starts_with("GET /task ") && has_session => ("200 OK", "[]"),
+                l if l.starts_with("GET /task ") => ("401 Unauthorized", ""),
+                l if l.starts_with("GET /open ") => ("200 OK", "[]"),
+                _ => ("404 Not Found", ""),
+            }
+        })
+        .await;
+
+        let route = |method: &str, route: &str, request_body: Value| RouteObject {
+            is_route_dynamic: false,
+            method: method.to_string(),
+            request_body,
+            response: json!("None"),
+            route: route.to_string(),
+        };
+        let credentials = json!({"username": "string", "password": "string"});
+        let endpoints = vec![
+            route("get", "/task", json!("None")),
+            route("get", "/open", json!("None")),
+            route("post", "/register", credentials.clone()),
+            route("post", "/login", credentials),
+        ];
+
+        let (report, token) = run_auth_checks(&Client::new(), &base_url, &endpoints).await;
+        assert_eq!(token.as_deref(), Some("secret"));
+        let failures = report.failures();
+        assert_eq!(failures.len(), 1, "{}", report);
+        assert_eq!(failures[0].route, "/open");
+        assert_eq!(failures[0].status, Some(200));
+        let failure = failures[0].failure.as_deref().unwrap_or_default();
+        assert!(failure.starts_with("expected 401 or 403"), "{}", failure);
+    }
+
+    #[tokio::test]
+    async fn test_logout_revokes_the_session() {
+        let logged_out = Arc::new(AtomicBool::new(false));
+        let revoked = logged_out.clone();
+        let base_url = serve(move |line, request| {
+            let has_session = request.contains("Bearer secret") && !revoked.load(Ordering::SeqCst);
+            match line {
+                _ if !has_session => ("401 Unauthorized", ""),
+                l if l.starts_with("POST /logout ") => {
+                    revoked.store(true, Ordering::SeqCst);
+                    ("200 OK", "")
+                }
+                l if l.starts_with("GET /task ") => ("200 OK", "[]"),
+                _ => ("404 Not Found", ""),
+            }
+        })
+        .await;
+
+        let route = |method: &str, route: &str| RouteObject {
+            is_route_dynamic: false,
+            method: method.to_string(),
+            request_body: json!("None"),
+            response: json!("None"),
+            route: route.to_string(),
+        };
+        let endpoints = vec![
+            route("post", "/logout"),
+            route("get", "/task"),
+            route("post", "/login"),
+        ];
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(
+            reqwest::header::AUTHORIZATION,
+            "Bearer secret".parse().unwrap(),
+        );
+        let client = Client::builder().default_headers(headers).build().unwrap();
+
+        // The logged in tests leave the session routes out, so the session lives until logout
+        let protected: Vec<RouteObject> = endpoints
+            .iter()
+            .filter(|endpoint| !is_session_route(&endpoint.route))
+            .cloned()
+            .collect();
+        let report = run_contract_tests(&client, &base_url, &protected).await;
+        assert!(report.failures().is_empty(), "{}", report);
+
+        let report = run_logout_check(&client, &base_url, &endpoints).await;
+        assert!(report.failures().is_empty(), "{}", report);
+        let paths: Vec<&str> = report.results.iter().map(|r| r.url_path.as_str()).collect();
+        assert_eq!(paths, vec!["/logout", "/task"]);
+        assert_eq!(report.results[1].status, Some(401));
+        assert!(logged_out.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,297 @@
+use crate::helpers::contract_tests::{is_float_type, is_integer_type, type_name};
+use crate::models::agents::agent_traits::{DatabaseSchema, Entity, EntityField};
+use serde::Deserialize;
+
+// First migration of the generated project, relative to its root
+pub const INITIAL_MIGRATION: &str = "migrations/0001_init.sql";
+
+// Dependency the generated project needs for a SQLite store, SQLite itself is compiled in
+pub const SQLITE_DEPENDENCY: (&str, &str) = (
+    "rusqlite",
+    r#"{ version = "0.32", features = ["bundled"] }"#,
+);
+
+// Where the generated server keeps its data
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DatabaseStore {
+    // `database.sqlite`, created by the migration when the server starts
+    Sqlite,
+    // `database.json`, like the code template
+    Json,
+}
+
+impl DatabaseStore {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Sqlite => "sqlite",
+            Self::Json => "json",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct DatabaseConfig {
+    pub store: DatabaseStore,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            store: DatabaseStore::Sqlite,
+        }
+    }
+}
+
+// `WorkoutSession` and `workout session` to `workout_session`
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    let mut previous_lower = false;
+    for c in name.trim().chars() {
+        if c.is_ascii_uppercase() && previous_lower {
+            snake.push('_');
+        }
+        match c {
+            c if c.is_ascii_alphanumeric() => snake.push(c.to_ascii_lowercase()),
+            _ if !snake.ends_with('_') => snake.push('_'),
+            _ => {}
+        }
+        previous_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+    }
+    snake.trim_matches('_').to_string()
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_lowercase() || c == '_')
+}
+
+// Names in snake case and the `id` fields left to the table, problems for the LLM to fix otherwise
+pub fn normalize_schema(schema: &DatabaseSchema) -> Result<DatabaseSchema, Vec<String>> {
+    let mut problems = vec![];
+    let mut entities: Vec<Entity> = vec![];
+
+    for entity in &schema.entities {
+        let name = snake_case(&entity.name);
+        if !is_identifier(&name) {
+            problems.push(format!("entity {:?} has no usable name", entity.name));
+            continue;
+        }
+        if entities.iter().any(|other| other.name == name) {
+            problems.push(format!("entity {} is defined twice", name));
+            continue;
+        }
+
+        let mut fields: Vec<EntityField> = vec![];
+        for field in &entity.fields {
+            let field_name = snake_case(&field.name);
+            if field_name == "id" {
+                continue;
+            }
+            if !is_identifier(&field_name) {
+                problems.push(format!(
+                    "field {:?} of {} has no usable name",
+                    field.name, name
+                ));
+            } else if fields.iter().any(|other| other.name == field_name) {
+                problems.push(format!("field {} of {} is defined twice", field_name, name));
+            } else {
+                fields.push(EntityField {
+                    name: field_name,
+                    references: field.references.as_deref().map(snake_case),
+                    ..field.clone()
+                });
+            }
+        }
+        entities.push(Entity { name, fields });
+    }
+
+    if entities.is_empty() && problems.is_empty() {
+        problems.push("the schema has no entities".to_string());
+    }
+    for entity in &entities {
+        for field in &entity.fields {
+            if let Some(target) = &field.references {
+                if !entities.iter().any(|other| &other.name == target) {
+                    problems.push(format!(
+                        "field {} of {} references {}, which is not an entity",
+                        field.name, entity.name, target
+                    ));
+                }
+            }
+        }
+    }
+
+    match problems.is_empty() {
+        true => Ok(DatabaseSchema { entities }),
+        false => Err(problems),
+    }
+}
+
+fn sql_type(field_type: &str) -> &'static str {
+    let name = type_name(field_type);
+    if name.contains("bool") || name.contains("int") || is_integer_type(&name) {
+        "INTEGER"
+    } else if name.contains("real") || name.contains("float") || is_float_type(&name) {
+        "REAL"
+    } else if name.contains("number") || name.contains("decimal") {
+        "NUMERIC"
+    } else if name.contains("blob") || name.contains("bytes") {
+        "BLOB"
+    } else {
+        // Strings, dates and times are stored as text
+        "TEXT"
+    }
+}
+
+// Names like `order` or `group` are SQL keywords, quoted they are plain identifiers
+fn quoted(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+// `CREATE TABLE` statements of a normalized schema, referenced tables first where possible
+pub fn migration_sql(schema: &DatabaseSchema) -> String {
+    let mut ordered: Vec<&Entity> = vec![];
+    let mut remaining: Vec<&Entity> = schema.entities.iter().collect();
+    while !remaining.is_empty() {
+        let ready = remaining.iter().position(|entity| {
+            entity.fields.iter().all(|field| match &field.references {
+                Some(target) => {
+                    target == &entity.name || ordered.iter().any(|done| &done.name == target)
+                }
+                None => true,
+            })
+        });
+        // Cycles are left in schema order, SQLite checks foreign keys on use, not on creation
+        ordered.push(remaining.remove(ready.unwrap_or(0)));
+    }
+
+    let tables: Vec<String> = ordered
+        .iter()
+        .map(|entity| {
+            let mut columns = vec!["    \"id\" INTEGER PRIMARY KEY AUTOINCREMENT".to_string()];
+            for field in &entity.fields {
+                let mut column = format!(
+                    "    {} {}",
+                    quoted(&field.name),
+                    sql_type(&field.field_type)
+                );
+                if !field.nullable {
+                    column.push_str(" NOT NULL");
+                }
+                if let Some(target) = &field.references {
+                    column.push_str(&format!(" REFERENCES {}(\"id\")", quoted(target)));
+                }
+                columns.push(column);
+            }
+            format!(
+                "CREATE TABLE IF NOT EXISTS {} (\n{}\n);\n",
+                quoted(&entity.name),
+                columns.join(",\n")
+            )
+        })
+        .collect();
+    tables.join("\n")
+}
+
+// Manifest with the dependency added under `[dependencies]`, nothing when it is already there or the manifest is not TOML
+pub fn add_dependency(manifest: &str, name: &str, spec: &str) -> Option<String> {
+    let parsed: toml::Table = toml::from_str(manifest).ok()?;
+    let present = parsed
+        .get("dependencies")
+        .and_then(|dependencies| dependencies.as_table())
+        .is_some_and(|dependencies| dependencies.contains_key(name));
+    if present {
+        return None;
+    }
+
+    let line = format!("{} = {}", name, spec);
+    let mut lines: Vec<&str> = manifest.lines().collect();
+    match lines.iter().position(|l| l.trim() == "[dependencies]") {
+        Some(header) => lines.insert(header + 1, &line),
+        None => {
+            lines.extend(["", "[dependencies]"]);
+            lines.push(&line);
+        }
+    }
+    Some(format!("{}\n", lines.join("\n")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_to_migration() {
+        let schema: DatabaseSchema = serde_json::from_str(
+            r#"{"entities": [
+                {"name": "WorkoutSession", "fields": [
+                    {"name": "id", "type": "integer"},
+                    {"name": "userId", "type": "integer", "references": "User"},
+                    {"name": "duration minutes", "type": "i64"},
+                    {"name": "notes", "type": "string", "nullable": "true"}
+                ]},
+                {"name": "User", "fields": [{"name": "email", "type": "text"}]}
+            ]}"#,
+        )
+        .unwrap();
+
+        let schema = normalize_schema(&schema).expect("Schema should be valid");
+        let sql = migration_sql(&schema);
+        assert!(sql.starts_with("CREATE TABLE IF NOT EXISTS \"user\" ("));
+        assert!(sql.contains(
+            "CREATE TABLE IF NOT EXISTS \"workout_session\" (\n    \"id\" INTEGER PRIMARY KEY AUTOINCREMENT,\n    \"user_id\" INTEGER NOT NULL REFERENCES \"user\"(\"id\"),\n    \"duration_minutes\" INTEGER NOT NULL,\n    \"notes\" TEXT\n);"
+        ));
+
+        let broken = DatabaseSchema {
+            entities: vec![Entity {
+                name: "task".to_string(),
+                fields: vec![EntityField {
+                    name: "owner".to_string(),
+                    field_type: "integer".to_string(),
+                    nullable: false,
+                    references: Some("Owner".to_string()),
+                }],
+            }],
+        };
+        assert_eq!(
+            normalize_schema(&broken),
+            Err(vec![
+                "field owner of task references owner, which is not an entity".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn test_keywords_are_quoted() {
+        let schema: DatabaseSchema = serde_json::from_str(
+            r#"{"entities": [
+                {"name": "Group", "fields": [{"name": "order", "type": "integer"}]},
+                {"name": "Order", "fields": [
+                    {"name": "index", "type": "integer"},
+                    {"name": "references", "type": "string"},
+                    {"name": "group", "type": "integer", "references": "Group"}
+                ]}
+            ]}"#,
+        )
+        .unwrap();
+
+        let sql = migration_sql(&normalize_schema(&schema).expect("Schema should be valid"));
+        assert!(sql.contains("CREATE TABLE IF NOT EXISTS \"group\" (\n    \"id\" INTEGER PRIMARY KEY AUTOINCREMENT,\n    \"order\" INTEGER NOT NULL\n);"));
+        assert!(sql.contains("CREATE TABLE IF NOT EXISTS \"order\" ("));
+        assert!(sql.contains("    \"index\" INTEGER NOT NULL,\n    \"references\" TEXT NOT NULL,\n    \"group\" INTEGER NOT NULL REFERENCES \"group\"(\"id\")"));
+    }
+
+    #[test]
+    fn test_add_dependency_once() {
+        let manifest = "[package]\nname = \"web_template\"\n\n[dependencies]\nserde = \"1\"\n";
+        let added = add_dependency(manifest, "rusqlite", "\"0.32\"").expect("Should be added");
+        assert_eq!(
+            added,
+            "[package]\nname = \"web_template\"\n\n[dependencies]\nrusqlite = \"0.32\"\nserde = \"1\"\n"
+        );
+        assert_eq!(add_dependency(&added, "rusqlite", "\"0.32\""), None);
+    }
+}
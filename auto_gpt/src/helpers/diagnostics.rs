@@ -0,0 +1,274 @@
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fmt;
+
+// Where the generated code is, as cargo reports it
+pub const MAIN_FILE: &str = "src/main.rs";
+
+// One line of `cargo build --message-format=json`, only compiler messages are used
+#[derive(Debug, Deserialize)]
+struct CargoMessage {
+    reason: String,
+    message: Option<Diagnostic>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DiagnosticCode {
+    pub code: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SpanText {
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DiagnosticSpan {
+    pub file_name: String,
+    pub byte_start: usize,
+    pub byte_end: usize,
+    pub line_start: usize,
+    pub column_start: usize,
+    pub is_primary: bool,
+    pub label: Option<String>,
+    pub suggested_replacement: Option<String>,
+    pub suggestion_applicability: Option<String>,
+    #[serde(default)]
+    pub text: Vec<SpanText>,
+}
+
+// A rustc diagnostic, its help and notes are children
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Diagnostic {
+    pub message: String,
+    pub code: Option<DiagnosticCode>,
+    pub level: String,
+    #[serde(default)]
+    pub spans: Vec<DiagnosticSpan>,
+    #[serde(default)]
+    pub children: Vec<Diagnostic>,
+}
+
+impl Diagnostic {
+    fn primary_span(&self) -> Option<&DiagnosticSpan> {
+        self.spans
+            .iter()
+            .find(|span| span.is_primary)
+            .or(self.spans.first())
+    }
+}
+
+// Compiler messages from the build's stdout, anything else is skipped
+pub fn parse_cargo_messages(stdout: &str) -> Vec<Diagnostic> {
+    stdout
+        .lines()
+        .filter_map(|line| serde_json::from_str::<CargoMessage>(line).ok())
+        .filter(|message| message.reason == "compiler-message")
+        .filter_map(|message| message.message)
+        .collect()
+}
+
+// Compile error as sent to the LLM
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompilerError {
+    pub code: Option<String>,
+    pub message: String,
+    // `file:line:column` of the primary span
+    pub location: Option<String>,
+    pub label: Option<String>,
+    // Source lines of the primary span
+    pub snippet: Vec<(usize, String)>,
+    // Help and notes, with the replacement rustc suggests
+    pub suggestions: Vec<String>,
+}
+
+impl CompilerError {
+    fn from_diagnostic(diagnostic: &Diagnostic) -> Self {
+        let span = diagnostic.primary_span();
+        let suggestions = diagnostic
+            .children
+            .iter()
+            .map(|child| {
+                let replacements: Vec<String> = child
+                    .spans
+                    .iter()
+                    .filter_map(|span| span.suggested_replacement.as_ref())
+                    .map(|replacement| format!("`{}`", replacement))
+                    .collect();
+                match replacements.is_empty() {
+                    true => format!("{}: {}", child.level, child.message),
+                    false => format!(
+                        "{}: {} - {}",
+                        child.level,
+                        child.message,
+                        replacements.join(", ")
+                    ),
+                }
+            })
+            .collect();
+
+        Self {
+            code: diagnostic.code.as_ref().map(|code| code.code.clone()),
+            message: diagnostic.message.clone(),
+            location: span.map(|span| {
+                format!(
+                    "{}:{}:{}",
+                    span.file_name, span.line_start, span.column_start
+                )
+            }),
+            label: span.and_then(|span| span.label.clone()),
+            snippet: span
+                .map(|span| {
+                    span.text
+                        .iter()
+                        .enumerate()
+                        .map(|(i, line)| (span.line_start + i, line.text.clone()))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            suggestions,
+        }
+    }
+}
+
+// Close to rustc's own rendering, without the column markers
+impl fmt::Display for CompilerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.code {
+            Some(code) => writeln!(f, "error[{}]: {}", code, self.message)?,
+            None => writeln!(f, "error: {}", self.message)?,
+        }
+        if let Some(location) = &self.location {
+            writeln!(f, "  --> {}", location)?;
+        }
+        for (line, text) in &self.snippet {
+            writeln!(f, "{:>5} | {}", line, text)?;
+        }
+        if let Some(label) = &self.label {
+            writeln!(f, "      = {}", label)?;
+        }
+        for suggestion in &self.suggestions {
+            writeln!(f, "      = {}", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+// Errors that point at code, once each - summaries such as "aborting due to ..." have no span
+pub fn compiler_errors(diagnostics: &[Diagnostic]) -> Vec<CompilerError> {
+    let mut seen = HashSet::new();
+    diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.level == "error" && !diagnostic.spans.is_empty())
+        .map(CompilerError::from_diagnostic)
+        .filter(|error| {
+            seen.insert((
+                error.code.clone(),
+                error.message.clone(),
+                error.location.clone(),
+            ))
+        })
+        .collect()
+}
+
+pub fn format_errors(errors: &[CompilerError]) -> String {
+    errors
+        .iter()
+        .map(|error| error.to_string())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn machine_applicable_edits<'a>(
+    diagnostic: &'a Diagnostic,
+    file_name: &str,
+    edits: &mut Vec<(usize, usize, &'a str)>,
+) {
+    for span in &diagnostic.spans {
+        if let (Some(replacement), Some("MachineApplicable")) = (
+            &span.suggested_replacement,
+            span.suggestion_applicability.as_deref(),
+        ) {
+            if span.file_name == file_name {
+                edits.push((span.byte_start, span.byte_end, replacement));
+            }
+        }
+    }
+    for child in &diagnostic.children {
+        machine_applicable_edits(child, file_name, edits);
+    }
+}
+
+// Apply the fixes rustc marks as machine applicable for errors in `file_name`
+// `code` must be the source that was compiled, None when there is nothing to apply
+pub fn apply_suggestions(
+    code: &str,
+    file_name: &str,
+    diagnostics: &[Diagnostic],
+) -> Option<String> {
+    let mut edits = vec![];
+    for diagnostic in diagnostics.iter().filter(|d| d.level == "error") {
+        machine_applicable_edits(diagnostic, file_name, &mut edits);
+    }
+    edits.sort();
+    edits.dedup();
+
+    // Edits are applied from the end so earlier offsets stay valid, overlapping ones are skipped
+    let mut fixed = code.to_string();
+    let mut applied = 0;
+    let mut limit = code.len();
+    for (start, end, replacement) in edits.into_iter().rev() {
+        let valid = start <= end
+            && end <= limit
+            && code.is_char_boundary(start)
+            && code.is_char_boundary(end);
+        if valid {
+            fixed.replace_range(start..end, replacement);
+            limit = start;
+            applied += 1;
+        }
+    }
+    (applied > 0).then_some(fixed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `let x = 5; x = 6;` built with `--message-format=json`, explanation and rendered text left out
+    const BUILD_STDOUT: &str = r#"{"reason":"compiler-message","package_id":"path+file:///tmp/server#0.1.0","message":{"$message_type":"diagnostic","children":[{"children":[],"code":null,"level":"help","message":"consider making this binding mutable","rendered":null,"spans":[{"byte_end":20,"byte_start":20,"column_end":9,"column_start":9,"expansion":null,"file_name":"src/main.rs","is_primary":true,"label":null,"line_end":2,"line_start":2,"suggested_replacement":"mut ","suggestion_applicability":"MachineApplicable","text":[{"highlight_end":9,"highlight_start":9,"text":"    let x = 5;"}]}]}],"level":"error","message":"cannot assign twice to immutable variable `x`","spans":[{"byte_end":21,"byte_start":20,"column_end":10,"column_start":9,"expansion":null,"file_name":"src/main.rs","is_primary":false,"label":"first assignment to `x`","line_end":2,"line_start":2,"suggested_replacement":null,"suggestion_applicability":null,"text":[{"highlight_end":10,"highlight_start":9,"text":"    let x = 5;"}]},{"byte_end":36,"byte_start":31,"column_end":10,"column_start":5,"expansion":null,"file_name":"src/main.rs","is_primary":true,"label":"cannot assign twice to immutable variable","line_end":3,"line_start":3,"suggested_replacement":null,"suggestion_applicability":null,"text":[{"highlight_end":10,"highlight_start":5,"text":"    x = 6;"}]}],"code":{"code":"E0384"}}}
+{"reason":"compiler-message","package_id":"path+file:///tmp/server#0.1.0","message":{"$message_type":"diagnostic","children":[],"level":"error","message":"aborting due to 1 previous error","spans":[],"code":null}}
+{"reason":"build-finished","success":false}"#;
+
+    const CODE: &str = "fn main() {\n    let x = 5;\n    x = 6;\n    println!(\"{}\", x);\n}\n";
+
+    #[test]
+    fn test_compiler_errors_from_cargo_json() {
+        let diagnostics = parse_cargo_messages(&format!("{}\n{}", BUILD_STDOUT, BUILD_STDOUT));
+        assert_eq!(diagnostics.len(), 4);
+
+        // Duplicates and the summary are dropped
+        let errors = compiler_errors(&diagnostics);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code.as_deref(), Some("E0384"));
+        assert_eq!(errors[0].location.as_deref(), Some("src/main.rs:3:5"));
+        assert_eq!(errors[0].snippet, vec![(3, "    x = 6;".to_string())]);
+
+        let formatted = format_errors(&errors);
+        assert!(formatted.starts_with("error[E0384]: cannot assign twice"));
+        assert!(formatted.contains("consider making this binding mutable - `mut `"));
+    }
+
+    #[test]
+    fn test_apply_machine_applicable_suggestions() {
+        let diagnostics = parse_cargo_messages(BUILD_STDOUT);
+        let fixed = apply_suggestions(CODE, MAIN_FILE, &diagnostics);
+        assert_eq!(
+            fixed.as_deref(),
+            Some("fn main() {\n    let mut x = 5;\n    x = 6;\n    println!(\"{}\", x);\n}\n")
+        );
+
+        // Nothing to apply to other files
+        assert_eq!(apply_suggestions(CODE, "src/lib.rs", &diagnostics), None);
+    }
+}
@@ -0,0 +1,347 @@
+use crate::models::agents::agent_traits::RouteObject;
+use crate::models::general::error::AutoGptError;
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+// Stands for an interpolated part of a fetch target
+const PARAM: &str = "{param}";
+
+// A `fetch(...)` call of the page, with interpolations replaced by `{param}`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FetchTarget {
+    pub method: String,
+    pub path: String,
+}
+
+impl fmt::Display for FetchTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.method, self.path)
+    }
+}
+
+// Text of a JS argument list up to its closing parenthesis, strings and nesting skipped over
+fn call_arguments(source: &str) -> &str {
+    let mut depth = 0;
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    for (i, c) in source.char_indices() {
+        if let Some(q) = quote {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                c if c == q => quote = None,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' | '\'' | '`' => quote = Some(c),
+            '(' | '{' | '[' => depth += 1,
+            ')' | '}' | ']' if depth > 0 => depth -= 1,
+            ')' => return &source[..i],
+            _ => {}
+        }
+    }
+    source
+}
+
+// The first argument, with `a + "b"` and `${a}` parts turned into `{param}`
+fn first_argument(arguments: &str) -> String {
+    let mut path = String::new();
+    let mut chars = arguments.trim_start().chars().peekable();
+    let mut first_part = true;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' | '\'' | '`' => {
+                let mut literal = String::new();
+                while let Some(next) = chars.next() {
+                    match next {
+                        '\\' => {
+                            if let Some(escaped) = chars.next() {
+                                literal.push(escaped);
+                            }
+                        }
+                        '$' if c == '`' && chars.peek() == Some(&'{') => {
+                            let mut depth = 0;
+                            for inner in chars.by_ref() {
+                                match inner {
+                                    '{' => depth += 1,
+                                    '}' if depth == 1 => break,
+                                    '}' => depth -= 1,
+                                    _ => {}
+                                }
+                            }
+                            // A leading interpolation is the base URL
+                            if !(first_part && literal.is_empty()) {
+                                literal.push_str(PARAM);
+                            }
+                        }
+                        next if next == c => break,
+                        next => literal.push(next),
+                    }
+                }
+                path.push_str(&literal);
+                first_part = false;
+            }
+            ',' => break,
+            c if c.is_whitespace() || c == '+' => {}
+            _ => {
+                // An identifier or call - the base URL when it comes first, a parameter otherwise
+                let mut depth = 0;
+                while let Some(&next) = chars.peek() {
+                    match next {
+                        '(' | '[' => depth += 1,
+                        ')' | ']' if depth > 0 => depth -= 1,
+                        '+' | ',' if depth == 0 => break,
+                        _ => {}
+                    }
+                    chars.next();
+                }
+                if !first_part {
+                    path.push_str(PARAM);
+                }
+                first_part = false;
+            }
+        }
+    }
+    path
+}
+
+// `http://localhost:8080/task?done=true` to `/task`
+fn normalize_path(target: &str) -> String {
+    let mut path = target;
+    if let Some(scheme_end) = path.find("://") {
+        let after_host = &path[scheme_end + 3..];
+        path = after_host.find('/').map_or("/", |i| &after_host[i..]);
+    }
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    match path.starts_with('/') {
+        true => path.to_string(),
+        false => format!("/{}", path),
+    }
+}
+
+fn request_method(options: &str) -> String {
+    let Some(start) = options.find("method") else {
+        return "GET".to_string();
+    };
+    options[start + "method".len()..]
+        .trim_start_matches(|c: char| c.is_whitespace() || c == ':' || c == '"' || c == '\'')
+        .chars()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .collect::<String>()
+        .to_uppercase()
+}
+
+// Every `fetch(...)` of a page, with its method
+pub fn fetch_targets(source: &str) -> Vec<FetchTarget> {
+    let mut targets = vec![];
+    let mut rest = source;
+    while let Some(start) = rest.find("fetch(") {
+        let preceded_by_name = rest[..start]
+            .chars()
+            .last()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_');
+        rest = &rest[start + "fetch(".len()..];
+        if preceded_by_name {
+            continue;
+        }
+
+        let arguments = call_arguments(rest);
+        let target = first_argument(arguments);
+        if target.is_empty() {
+            continue;
+        }
+        let options = arguments.split_once(',').map_or("", |(_, options)| options);
+        let target = FetchTarget {
+            method: request_method(options),
+            path: normalize_path(&target),
+        };
+        if !targets.contains(&target) {
+            targets.push(target);
+        }
+    }
+    targets
+}
+
+fn route_matches(route: &str, path: &str) -> bool {
+    let route: Vec<&str> = route.trim_end_matches('/').split('/').collect();
+    let path: Vec<&str> = path.trim_end_matches('/').split('/').collect();
+    route.len() == path.len()
+        && route.iter().zip(&path).all(|(expected, actual)| {
+            let dynamic = expected.starts_with('{') && expected.ends_with('}');
+            dynamic || expected == actual
+        })
+}
+
+// Fetch targets that no route of the schema answers
+pub fn unknown_targets<'a>(
+    targets: &'a [FetchTarget],
+    routes: &[RouteObject],
+) -> Vec<&'a FetchTarget> {
+    targets
+        .iter()
+        .filter(|target| {
+            !routes.iter().any(|route| {
+                route.method.eq_ignore_ascii_case(&target.method)
+                    && route_matches(&route.route, &target.path)
+            })
+        })
+        .collect()
+}
+
+// Serves the files of a directory on localhost until dropped
+#[derive(Debug)]
+pub struct StaticServer {
+    port: u16,
+    handle: JoinHandle<()>,
+}
+
+impl StaticServer {
+    pub async fn start(dir: &Path) -> Result<Self, AutoGptError> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .await
+            .map_err(AutoGptError::io("static server"))?;
+        let port = listener
+            .local_addr()
+            .map_err(AutoGptError::io("static server"))?
+            .port();
+
+        let dir = dir.to_path_buf();
+        let handle = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve_file(stream, dir.clone()));
+            }
+        });
+        Ok(Self { port, handle })
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("http://127.0.0.1:{}{}", self.port, path)
+    }
+}
+
+impl Drop for StaticServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+// Answer one GET with a file of `dir`, `/` being index.html
+async fn serve_file(mut stream: TcpStream, dir: PathBuf) {
+    let mut buf = vec![0; 8192];
+    let n = stream.read(&mut buf).await.unwrap_or(0);
+    let request = String::from_utf8_lossy(&buf[..n]);
+    let path = request
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .unwrap_or("/")
+        .split('?')
+        .next()
+        .unwrap_or("/");
+
+    let relative = match path.trim_start_matches('/') {
+        "" => "index.html",
+        relative => relative,
+    };
+    // Nothing outside the directory is served
+    let file = match relative.split('/').any(|part| part == "..") {
+        true => None,
+        false => tokio::fs::read(dir.join(relative)).await.ok(),
+    };
+    let (status, content_type, body) = match file {
+        Some(body) => {
+            let content_type = match Path::new(relative).extension().and_then(|e| e.to_str()) {
+                Some("html") => "text/html; charset=utf-8",
+                Some("js") => "text/javascript",
+                Some("css") => "text/css",
+                _ => "application/octet-stream",
+            };
+            ("200 OK", content_type, body)
+        }
+        None => ("404 Not Found", "text/plain", b"Not found".to_vec()),
+    };
+
+    let head = format!(
+        "HTTP/1.1 {}\r\ncontent-type: {}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    );
+    let _ = stream.write_all(head.as_bytes()).await;
+    let _ = stream.write_all(&body).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_fetch_targets_against_schema() {
+        let page = r#"
+<script>
+const API_BASE_URL = window.API_BASE_URL || "http://localhost:8080";
+async function load() {
+  const res = await fetch(`${API_BASE_URL}/task`);
+  await fetch(`${API_BASE_URL}/task/${task.id}`, { method: "DELETE" });
+  await fetch(API_BASE_URL + "/task/" + encodeURIComponent(id), {
+    method: 'PUT',
+    headers: { "Content-Type": "application/json" },
+    body: JSON.stringify({ name: "a,b" }),
+  });
+  await fetch("http://localhost:8080/tasks?done=true");
+  prefetch("/ignored");
+}
+</script>"#;
+        let targets = fetch_targets(page);
+        let found: Vec<String> = targets.iter().map(|t| t.to_string()).collect();
+        assert_eq!(
+            found,
+            vec![
+                "GET /task",
+                "DELETE /task/{param}",
+                "PUT /task/{param}",
+                "GET /tasks"
+            ]
+        );
+
+        let route = |method: &str, route: &str| RouteObject {
+            is_route_dynamic: route.contains('{'),
+            method: method.to_string(),
+            request_body: json!("None"),
+            response: json!("None"),
+            route: route.to_string(),
+        };
+        let routes = vec![
+            route("get", "/task"),
+            route("delete", "/task/{id}"),
+            route("get", "/task/{id}"),
+        ];
+        let unknown: Vec<String> = unknown_targets(&targets, &routes)
+            .iter()
+            .map(|t| t.to_string())
+            .collect();
+        assert_eq!(unknown, vec!["PUT /task/{param}", "GET /tasks"]);
+    }
+
+    #[tokio::test]
+    async fn test_static_server_serves_index() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.html"), "<h1>Tasks</h1>").unwrap();
+
+        let server = StaticServer::start(dir.path()).await.expect("No server");
+        let index = reqwest::get(server.url("/")).await.unwrap();
+        assert_eq!(index.status(), 200);
+        assert_eq!(index.text().await.unwrap(), "<h1>Tasks</h1>");
+
+        let missing = reqwest::get(server.url("/../Cargo.toml")).await.unwrap();
+        assert_eq!(missing.status(), 404);
+    }
+}
@@ -1,20 +1,35 @@
 use std::fs;
+use std::path::Path;
 
 use reqwest::Client;
 use serde::de::DeserializeOwned;
 
-use crate::{apis::call_request::call_gpt, models::general::llm::Message};
+use crate::apis::call_request::{call_gpt, call_gpt_stream};
+use crate::apis::llm_provider::llm_provider;
+use crate::apis::retry::with_retry;
+use crate::apis::usage::{usage_ledger, UsageRecord};
+use crate::models::agent_basic::agent_memory::estimate_tokens;
+use crate::models::agent_basic::basic_agent::BasicAgent;
+use crate::models::general::{
+    error::AutoGptError,
+    llm::{Message, TokenUsage},
+};
 
 use super::command_line::PrintCommand;
+use super::config::workspace_config;
+use super::database_schema::add_dependency;
+use super::response_parsing::{decode_llm_json, extract_rust_code, validate_rust_file};
+use crate::ai_functions::aifunc_backend::print_fixed_code;
 
-const CODE_TEMPLATE_PATH: &str =
-    "/users/s0046425/git_projects/2025/coursera-courses/web_template/src/code_template.rs";
-pub const WEB_SERVER_PROJECT_PATH: &str =
-    "/users/s0046425/git_projects/2025/coursera-courses/web_template/";
-const EXEC_MAIN_PATH: &str =
-    "/users/s0046425/git_projects/2025/coursera-courses/web_template/src/main.rs";
-const API_SCHEMA_PATH: &str =
-    "/users/s0046425/git_projects/2025/coursera-courses/auto_gpt/schemas/api_schema.json";
+// How many times a response that is not valid JSON is sent back to the LLM
+const MAX_JSON_REPAIR_ROUNDS: u8 = 2;
+
+// How many times code that does not parse is sent back to the LLM
+const MAX_CODE_REPAIR_ROUNDS: u8 = 2;
+
+// What the authentication module uses, argon2's `std` feature brings the OS random number generator
+const AUTH_DEPENDENCIES: [(&str, &str); 1] =
+    [("argon2", r#"{ version = "0.5", features = ["std"] }"#)];
 
 // Takes a function from `ai_functions` and a dummy input, generate a prompt for LLM
 pub fn extend_ai_function(ai_func: fn(&str) -> &'static str, func_input: &str) -> Message {
@@ -36,40 +51,165 @@ pub fn extend_ai_function(ai_func: fn(&str) -> &'static str, func_input: &str) -
 }
 
 // Perform call to LLM
+// The agent's earlier prompts and responses are sent first, and this call is added to its memory
+// Token usage is recorded against the agent and function, the call fails once the budget is spent
 pub async fn ai_task_request(
     msg_context: String,
-    agent_position: &str,
+    agent: &mut BasicAgent,
     agent_operation: &str,
     function_pass: for<'a> fn(&'a str) -> &'static str,
-) -> String {
-    let extended_msg = extend_ai_function(function_pass, &msg_context);
+) -> Result<String, AutoGptError> {
+    let config = workspace_config()?;
+    let ledger = usage_ledger();
+    config.costs.check_budget(ledger.total_cost())?;
 
-    PrintCommand::AICall.print_agent_message(agent_position, agent_operation);
+    let agent_position = agent.position.clone();
+    let mut messages = agent.recall(&config.memory);
+    messages.push(extend_ai_function(function_pass, &msg_context));
 
-    let llm_response_res = call_gpt(vec![extended_msg.clone()]).await;
+    PrintCommand::AICall.print_agent_message(&agent_position, agent_operation);
 
-    // Handle error- call it again and if error again, panic
-    match llm_response_res {
-        Ok(llm_response) => llm_response,
-        Err(_) => call_gpt(vec![extended_msg.clone()])
-            .await
-            .expect("Failed twice to call LLM"),
-    }
+    // Calls go through `call_gpt`, the provider is looked up here for its model name
+    let provider = llm_provider().map_err(|e| AutoGptError::Config(e.to_string()))?;
+    let retry_policy = config.retry.for_agent(&agent_position);
+
+    let print_token = |token: &str| PrintCommand::AICall.print_stream_token(token);
+
+    // Retry rate limits and server errors, give up straight away on bad requests
+    let llm_response = with_retry(
+        &retry_policy,
+        || async {
+            match config.stream {
+                true => {
+                    let res =
+                        call_gpt_stream(agent_operation, messages.clone(), &print_token).await;
+                    PrintCommand::AICall.print_stream_end();
+                    res
+                }
+                false => call_gpt(agent_operation, messages.clone()).await,
+            }
+        },
+        |e, delay| {
+            PrintCommand::Issue.print_agent_message(
+                &agent_position,
+                format!("LLM call failed ({}), retrying in {:?}", e, delay).as_str(),
+            )
+        },
+    )
+    .await?;
+
+    // Estimate from the text when the provider does not report usage
+    let (usage, estimated) = match llm_response.usage {
+        Some(usage) => (usage, false),
+        None => {
+            let prompt_tokens: usize = messages.iter().map(|m| estimate_tokens(&m.content)).sum();
+            let usage = TokenUsage {
+                prompt_tokens: prompt_tokens as u64,
+                completion_tokens: estimate_tokens(&llm_response.content) as u64,
+            };
+            (usage, true)
+        }
+    };
+    ledger.record(UsageRecord {
+        agent: agent_position,
+        function: agent_operation.to_string(),
+        model: provider.model().to_string(),
+        usage,
+        estimated,
+        cost_usd: config.costs.cost(provider.model(), &usage),
+    });
+
+    agent.remember(
+        format!("{}: {}", agent_operation, msg_context),
+        llm_response.content.clone(),
+    );
+    Ok(llm_response.content)
 }
 
 // Perform call to LLM - Decoded
+// Invalid JSON is sent back to the LLM with the parse error, up to MAX_JSON_REPAIR_ROUNDS times
 pub async fn ai_task_request_decoded<T: DeserializeOwned>(
     msg_context: String,
-    agent_position: &str,
+    agent: &mut BasicAgent,
     agent_operation: &str,
     function_pass: for<'a> fn(&'a str) -> &'static str,
-) -> T {
-    let llm_response =
-        ai_task_request(msg_context, agent_position, agent_operation, function_pass).await;
+) -> Result<T, AutoGptError> {
+    let mut llm_response =
+        ai_task_request(msg_context.clone(), agent, agent_operation, function_pass).await?;
+
+    let mut repair_round = 0;
+    loop {
+        match decode_llm_json::<T>(&llm_response) {
+            Ok(decoded_response) => return Ok(decoded_response),
+            Err(source) if repair_round < MAX_JSON_REPAIR_ROUNDS => {
+                repair_round += 1;
+                PrintCommand::Issue.print_agent_message(
+                    &agent.position,
+                    format!("Invalid JSON from LLM ({}), asking for a fix", source).as_str(),
+                );
 
-    let decoded_response: T = serde_json::from_str(llm_response.as_str())
-        .expect("Failed to decode a LLM response from serde_json");
-    decoded_response
+                let repair_context = format!(
+                    "{} \n YOUR PREVIOUS OUTPUT: {} \n JSON PARSE ERROR: {} \n
+                    PRINT ONLY VALID JSON. NO CODE FENCES. NO COMMENTARY.",
+                    msg_context, llm_response, source
+                );
+                llm_response =
+                    ai_task_request(repair_context, agent, agent_operation, function_pass).await?;
+            }
+            Err(source) => {
+                return Err(AutoGptError::Decode {
+                    function: agent_operation.to_string(),
+                    source,
+                })
+            }
+        }
+    }
+}
+
+// Perform call to LLM - Rust code
+// Code is pulled out of fences and must parse as a Rust file, otherwise it goes to `print_fixed_code`
+pub async fn ai_task_request_rust_code(
+    msg_context: String,
+    agent: &mut BasicAgent,
+    agent_operation: &str,
+    function_pass: for<'a> fn(&'a str) -> &'static str,
+) -> Result<String, AutoGptError> {
+    let llm_response = ai_task_request(msg_context, agent, agent_operation, function_pass).await?;
+    let mut code = extract_rust_code(&llm_response);
+
+    let mut repair_round = 0;
+    loop {
+        match validate_rust_file(&code) {
+            Ok(()) => return Ok(code),
+            Err(e) if repair_round < MAX_CODE_REPAIR_ROUNDS => {
+                repair_round += 1;
+                PrintCommand::Issue.print_agent_message(
+                    &agent.position,
+                    format!("Generated code does not parse ({}), asking for a fix", e).as_str(),
+                );
+
+                let repair_context = format!(
+                    "BROKE_CODE: {} \n ERROR_BUGS: {} \n
+                    THIS FUNCTIONS ONLY OUTPUTS CODE. JUST OUTPUT THE CODE.",
+                    code, e
+                );
+                let llm_response = ai_task_request(
+                    repair_context,
+                    agent,
+                    get_function_string!(print_fixed_code),
+                    print_fixed_code,
+                )
+                .await?;
+                code = extract_rust_code(&llm_response);
+            }
+            Err(e) => {
+                return Err(AutoGptError::BuildFailure(format!(
+                    "Generated code does not parse as Rust: {}",
+                    e
+                )))
+            }
+        }
+    }
 }
 
 // Check if url is valid
@@ -78,34 +218,106 @@ pub async fn check_status_code(client: &Client, url: &str) -> Result<u16, reqwes
     Ok(response.status().as_u16())
 }
 
+fn read_file(path: &Path) -> Result<String, AutoGptError> {
+    fs::read_to_string(path).map_err(AutoGptError::io(path.display().to_string()))
+}
+
+// Write a file, creating its parent directories
+pub fn write_file(path: &Path, contents: &str) -> Result<(), AutoGptError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(AutoGptError::io(parent.display().to_string()))?;
+    }
+    fs::write(path, contents).map_err(AutoGptError::io(path.display().to_string()))
+}
+
 // Get code template
-pub fn read_code_template_contents() -> String {
-    let path = String::from(CODE_TEMPLATE_PATH);
-    fs::read_to_string(path).expect("Failed to read code template")
+pub fn read_code_template_contents() -> Result<String, AutoGptError> {
+    read_file(&workspace_config()?.code_template_path)
 }
 
 // Get exec main
-pub fn read_exec_main_contents() -> String {
-    let path = String::from(EXEC_MAIN_PATH);
-    fs::read_to_string(path).expect("Failed to read main.rs file")
+pub fn read_exec_main_contents() -> Result<String, AutoGptError> {
+    read_file(&workspace_config()?.exec_main_path())
 }
 
 // Save new backend code
-pub fn save_backend_code(contents: &String) {
-    let path = String::from(EXEC_MAIN_PATH);
-    fs::write(path, contents).expect("Failed to write main.rs file")
+pub fn save_backend_code(contents: &str) -> Result<(), AutoGptError> {
+    write_file(&workspace_config()?.exec_main_path(), contents)
 }
 
 // Save JSON API endpoint schema
-pub fn save_api_endpoints(api_endpoints: &String) {
-    let path = String::from(API_SCHEMA_PATH);
-    fs::write(path, api_endpoints).expect("Failed to write api endpoints JSON file")
+pub fn save_api_endpoints(api_endpoints: &str) -> Result<(), AutoGptError> {
+    write_file(&workspace_config()?.api_schema_path, api_endpoints)
+}
+
+// Save the frontend page
+pub fn save_frontend_code(contents: &str) -> Result<(), AutoGptError> {
+    write_file(
+        &workspace_config()?.frontend_dir().join("index.html"),
+        contents,
+    )
+}
+
+// Install the authentication module into the generated project, returning its source
+pub fn install_auth_module() -> Result<String, AutoGptError> {
+    let config = workspace_config()?;
+    let module = read_file(&config.auth_module_path)?;
+    write_file(&config.project_auth_module_path(), &module)?;
+    for (name, spec) in AUTH_DEPENDENCIES {
+        add_project_dependency(name, spec)?;
+    }
+    Ok(module)
+}
+
+// Save the entities of the database designer
+pub fn save_database_schema(schema: &str) -> Result<(), AutoGptError> {
+    write_file(&workspace_config()?.database_schema_path(), schema)
+}
+
+// Save the migration that creates the database tables
+pub fn save_migration(sql: &str) -> Result<(), AutoGptError> {
+    write_file(&workspace_config()?.migration_path(), sql)
+}
+
+// Add a dependency to the generated project, true when its manifest changed
+pub fn add_project_dependency(name: &str, spec: &str) -> Result<bool, AutoGptError> {
+    let manifest_path = workspace_config()?.project_manifest_path();
+    match add_dependency(&read_file(&manifest_path)?, name, spec) {
+        Some(manifest) => write_file(&manifest_path, &manifest).map(|()| true),
+        None => Ok(false),
+    }
+}
+
+// Get the integration tests of the generated project, as they will be run
+pub fn read_integration_tests() -> Result<String, AutoGptError> {
+    read_file(&workspace_config()?.integration_tests_path())
+}
+
+// Save the integration tests of the generated project
+pub fn save_integration_tests(contents: &str) -> Result<(), AutoGptError> {
+    write_file(&workspace_config()?.integration_tests_path(), contents)
+}
+
+// Save the endpoint schema as an OpenAPI document
+pub fn save_openapi_document(document: &str) -> Result<(), AutoGptError> {
+    write_file(&workspace_config()?.openapi_path(), document)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ai_functions::aifunc_architect::print_project_scope;
+    use crate::ai_functions::aifunc_backend::print_backend_webserver_code;
     use crate::ai_functions::aifunc_managing::convert_user_input_to_goal;
+    use crate::apis::llm_provider::with_llm_provider;
+    use crate::apis::mock_provider::{fixture_provider, MockProvider};
+    use crate::apis::usage::{with_usage_ledger, ModelPrice, UsageLedger};
+    use crate::helpers::config::{scratch_workspace, with_workspace_config, WorkspaceConfig};
+    use crate::models::agent_basic::basic_traits::BasicTraits;
+    use crate::models::agents::agent_traits::ProjectScope;
+    use std::path::Path;
+    use std::process::Command;
+    use std::sync::Arc;
 
     #[test]
     fn test_extend_ai_function() {
@@ -116,13 +328,170 @@ mod tests {
     #[tokio::test]
     async fn test_ai_task_request() {
         let ai_func_param = "Build a website for making stock price requests".to_string();
-        let llm_response = ai_task_request(
-            ai_func_param,
-            "Managing Agent",
-            "Defining user requirements",
-            convert_user_input_to_goal,
+        let mut agent = BasicAgent::new("Manage agents".to_string(), "Managing Agent".to_string());
+        let llm_response = with_llm_provider(
+            fixture_provider(),
+            ai_task_request(
+                ai_func_param,
+                &mut agent,
+                get_function_string!(convert_user_input_to_goal),
+                convert_user_input_to_goal,
+            ),
         )
-        .await;
+        .await
+        .expect("Failed to call LLM");
         assert!(llm_response.len() > 20);
+        assert_eq!(agent.memory.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_ai_task_request_stops_at_budget() {
+        let provider = Arc::new(MockProvider::new().with_response("print_project_scope", "{}"));
+        let ledger = Arc::new(UsageLedger::default());
+        let (_project, mut config) = scratch_workspace();
+        config.costs.prices.insert(
+            "mock".to_string(),
+            ModelPrice {
+                prompt_per_1k: 1.0,
+                completion_per_1k: 1.0,
+            },
+        );
+        config.costs.budget_usd = Some(0.001);
+
+        let mut agent = BasicAgent::new(
+            "Scope projects".to_string(),
+            "Solutions Architect".to_string(),
+        );
+        let results = with_workspace_config(
+            config,
+            with_usage_ledger(
+                ledger.clone(),
+                with_llm_provider(provider.clone(), async {
+                    let mut results = vec![];
+                    for _ in 0..2 {
+                        results.push(
+                            ai_task_request(
+                                "Build a todo app".to_string(),
+                                &mut agent,
+                                get_function_string!(print_project_scope),
+                                print_project_scope,
+                            )
+                            .await,
+                        );
+                    }
+                    results
+                }),
+            ),
+        )
+        .await;
+
+        assert!(results[0].is_ok());
+        assert!(matches!(
+            results[1],
+            Err(AutoGptError::BudgetExceeded { .. })
+        ));
+        assert_eq!(provider.calls().len(), 1);
+        let records = ledger.records();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].agent, "Solutions Architect");
+        assert_eq!(records[0].function, "print_project_scope");
+        assert!(records[0].estimated);
+    }
+
+    #[tokio::test]
+    async fn test_ai_task_request_decoded_repairs_json() {
+        let provider = Arc::new(
+            MockProvider::new()
+                .with_response("print_project_scope", "I think the scope is: {\"is_crud_required\": tru")
+                .with_response(
+                    "print_project_scope",
+                    "```json\n{\"is_crud_required\": true, \"is_user_login_and_logout\": false, \"is_external_urls_required\": false}\n```",
+                ),
+        );
+
+        let mut agent = BasicAgent::new(
+            "Scope projects".to_string(),
+            "Solutions Architect".to_string(),
+        );
+        let scope: ProjectScope = with_llm_provider(
+            provider.clone(),
+            ai_task_request_decoded(
+                "Build a todo app".to_string(),
+                &mut agent,
+                get_function_string!(print_project_scope),
+                print_project_scope,
+            ),
+        )
+        .await
+        .expect("Failed to repair JSON");
+
+        assert!(scope.is_crud_required);
+        assert_eq!(provider.calls().len(), 2);
+        // The repair call sees the broken attempt in the agent's history
+        let repair_messages = &provider.messages()[1];
+        assert_eq!(repair_messages.len(), 3);
+        assert_eq!(repair_messages[1].role, "assistant");
+    }
+
+    #[tokio::test]
+    async fn test_ai_task_request_rust_code_repairs_code() {
+        let provider = Arc::new(
+            MockProvider::new()
+                .with_response(
+                    "print_backend_webserver_code",
+                    "```rust\nfn main() {\n    println!(\"hi\")\n```",
+                )
+                .with_response(
+                    "print_fixed_code",
+                    "Fixed:\n```rust\nfn main() {\n    println!(\"hi\");\n}\n```",
+                ),
+        );
+
+        let mut agent = BasicAgent::new("Write code".to_string(), "Backend Developer".to_string());
+        let code = with_llm_provider(
+            provider.clone(),
+            ai_task_request_rust_code(
+                "CODE TEMPLATE: fn main() {}".to_string(),
+                &mut agent,
+                get_function_string!(print_backend_webserver_code),
+                print_backend_webserver_code,
+            ),
+        )
+        .await
+        .expect("Failed to repair code");
+
+        assert!(code.starts_with("fn main()"));
+        assert_eq!(
+            provider.calls(),
+            vec!["print_backend_webserver_code", "print_fixed_code"]
+        );
+    }
+
+    // The module copied into projects is only checked here, the first run builds actix-web
+    #[test]
+    #[ignore = "builds actix-web and argon2, run with --ignored"]
+    fn test_auth_module_builds_and_guards_routes() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let config = WorkspaceConfig::default();
+        assert_eq!(
+            root.join(&config.auth_module_path).canonicalize().unwrap(),
+            root.join("../web_template/src/auth.rs")
+                .canonicalize()
+                .unwrap()
+        );
+
+        let output = Command::new("cargo")
+            .arg("test")
+            .arg("--manifest-path")
+            .arg(root.join("fixtures/auth_module/Cargo.toml"))
+            .env("CARGO_TARGET_DIR", root.join("target/auth_module"))
+            .output()
+            .expect("Failed to run cargo test");
+        assert!(
+            output.status.success(),
+            "{}\n{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
     }
 }
@@ -1,2 +1,13 @@
 pub mod command_line;
+pub mod config;
+pub mod contract_tests;
+pub mod database_schema;
+pub mod diagnostics;
+pub mod frontend_check;
 pub mod general;
+pub mod openapi;
+pub mod response_parsing;
+pub mod route_extraction;
+pub mod safety_scan;
+pub mod sandbox;
+pub mod test_report;
@@ -0,0 +1,197 @@
+use crate::helpers::contract_tests::{is_float_type, is_integer_type, is_unspecified, type_name};
+use crate::models::agents::agent_traits::RouteObject;
+use serde_json::{json, Map, Value};
+
+pub const OPENAPI_VERSION: &str = "3.1.0";
+
+// JSON Schema of a shape such as `{"id": "number", "tags": ["string"]}`
+pub fn json_schema(shape: &Value) -> Value {
+    match shape {
+        Value::Object(fields) => {
+            let properties: Map<String, Value> = fields
+                .iter()
+                .map(|(key, field)| (key.clone(), json_schema(field)))
+                .collect();
+            let required: Vec<&String> = fields
+                .iter()
+                .filter(|(_, field)| !is_nullable(field))
+                .map(|(key, _)| key)
+                .collect();
+            json!({"type": "object", "properties": properties, "required": required})
+        }
+        Value::Array(items) => match items.first() {
+            Some(item) => json!({"type": "array", "items": json_schema(item)}),
+            None => json!({"type": "array"}),
+        },
+        Value::String(name) => type_schema(&type_name(name)),
+        // Anything the schema does not describe is left open
+        _ => json!({}),
+    }
+}
+
+fn is_nullable(shape: &Value) -> bool {
+    matches!(shape, Value::String(name) if type_name(name).starts_with("option"))
+}
+
+fn type_schema(name: &str) -> Value {
+    if let Some(inner) = name
+        .strip_prefix("option<")
+        .and_then(|inner| inner.strip_suffix('>'))
+    {
+        let mut schema = type_schema(inner);
+        if let Some(Value::String(ty)) = schema.get("type").cloned() {
+            schema["type"] = json!([ty, "null"]);
+        }
+        return schema;
+    }
+
+    if name.contains("bool") {
+        json!({"type": "boolean"})
+    } else if is_integer_type(name) || name.contains("int") {
+        json!({"type": "integer"})
+    } else if is_float_type(name) || name.contains("number") || name.contains("float") {
+        json!({"type": "number"})
+    } else if name.contains("string") || name == "str" {
+        json!({"type": "string"})
+    } else if name.contains("vec") || name.contains("array") || name.contains("list") {
+        json!({"type": "array"})
+    } else if name.contains("object") || name.contains("map") {
+        json!({"type": "object"})
+    } else {
+        json!({})
+    }
+}
+
+// `/task/{id}` has the path parameter `id`
+fn path_parameters(route: &str) -> Vec<Value> {
+    route
+        .split('/')
+        .filter_map(|segment| segment.strip_prefix('{')?.strip_suffix('}'))
+        .map(|name| {
+            json!({
+                "name": name,
+                "in": "path",
+                "required": true,
+                "schema": {"type": "string"},
+            })
+        })
+        .collect()
+}
+
+// `post` of `/task/{id}` to `post_task_id`
+fn operation_id(method: &str, route: &str) -> String {
+    let path: Vec<&str> = route
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect();
+    match path.is_empty() {
+        true => format!("{}_root", method),
+        false => format!("{}_{}", method, path.join("_")),
+    }
+}
+
+fn operation(route: &RouteObject, method: &str) -> Value {
+    let mut operation = Map::new();
+    operation.insert(
+        "operationId".to_string(),
+        json!(operation_id(method, &route.route)),
+    );
+
+    let parameters = path_parameters(&route.route);
+    if !parameters.is_empty() {
+        operation.insert("parameters".to_string(), json!(parameters));
+    }
+    if !is_unspecified(&route.request_body) {
+        operation.insert(
+            "requestBody".to_string(),
+            json!({
+                "required": true,
+                "content": {"application/json": {"schema": json_schema(&route.request_body)}},
+            }),
+        );
+    }
+
+    let mut success = json!({"description": "Successful response"});
+    if !is_unspecified(&route.response) {
+        success["content"] = json!({"application/json": {"schema": json_schema(&route.response)}});
+    }
+    operation.insert("responses".to_string(), json!({"200": success}));
+    Value::Object(operation)
+}
+
+// OpenAPI document of the generated server's endpoints
+pub fn openapi_document(description: &str, routes: &[RouteObject]) -> Value {
+    let mut paths = Map::new();
+    for route in routes {
+        let method = route.method.to_lowercase();
+        let Value::Object(path_item) = paths
+            .entry(route.route.clone())
+            .or_insert_with(|| json!({}))
+        else {
+            continue;
+        };
+        path_item.insert(method.clone(), operation(route, &method));
+    }
+
+    json!({
+        "openapi": OPENAPI_VERSION,
+        "info": {
+            "title": "Generated web server",
+            "description": description,
+            "version": "0.1.0",
+        },
+        "paths": paths,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_openapi_document_from_routes() {
+        let task =
+            json!({"id": "number", "name": "string", "done": "bool", "note": "Option<String>"});
+        let routes = vec![
+            RouteObject {
+                is_route_dynamic: false,
+                method: "post".to_string(),
+                request_body: task.clone(),
+                response: json!("None"),
+                route: "/task".to_string(),
+            },
+            RouteObject {
+                is_route_dynamic: true,
+                method: "GET".to_string(),
+                request_body: json!("None"),
+                response: json!([task]),
+                route: "/task/{id}".to_string(),
+            },
+        ];
+
+        let document = openapi_document("a todo app", &routes);
+        assert_eq!(document["openapi"], "3.1.0");
+
+        let post = &document["paths"]["/task"]["post"];
+        assert_eq!(post["operationId"], "post_task");
+        let body = &post["requestBody"]["content"]["application/json"]["schema"];
+        assert_eq!(body["properties"]["done"], json!({"type": "boolean"}));
+        assert_eq!(
+            body["properties"]["note"],
+            json!({"type": ["string", "null"]})
+        );
+        assert_eq!(body["required"], json!(["done", "id", "name"]));
+        assert!(post["responses"]["200"].get("content").is_none());
+
+        let get = &document["paths"]["/task/{id}"]["get"];
+        assert_eq!(get["parameters"][0]["name"], "id");
+        assert_eq!(get["parameters"][0]["in"], "path");
+        assert!(get.get("requestBody").is_none());
+        let response = &get["responses"]["200"]["content"]["application/json"]["schema"];
+        assert_eq!(response["type"], "array");
+        assert_eq!(
+            response["items"]["properties"]["name"],
+            json!({"type": "string"})
+        );
+    }
+}
@@ -0,0 +1,159 @@
+use serde::de::DeserializeOwned;
+
+// Remove a surrounding markdown code fence (```json ... ```), if there is one
+pub fn strip_code_fences(response: &str) -> &str {
+    let trimmed = response.trim();
+    let Some(start) = trimmed.find("```") else {
+        return trimmed;
+    };
+
+    // Skip the fence and its language tag
+    let after_fence = &trimmed[start + 3..];
+    let body_start = after_fence.find('\n').map(|i| i + 1).unwrap_or(0);
+    let body = &after_fence[body_start..];
+
+    match body.find("```") {
+        Some(end) => body[..end].trim(),
+        None => body.trim(),
+    }
+}
+
+// All fenced code blocks in a response as (language tag, body)
+fn fenced_blocks(response: &str) -> Vec<(&str, &str)> {
+    let mut blocks = vec![];
+    let mut rest = response;
+
+    while let Some(start) = rest.find("```") {
+        let after_fence = &rest[start + 3..];
+        let Some(tag_end) = after_fence.find('\n') else {
+            break;
+        };
+        let tag = after_fence[..tag_end].trim();
+        let body = &after_fence[tag_end + 1..];
+        match body.find("```") {
+            Some(end) => {
+                blocks.push((tag, &body[..end]));
+                rest = &body[end + 3..];
+            }
+            // Unterminated fence - the model was cut off, keep what we have
+            None => {
+                blocks.push((tag, body));
+                break;
+            }
+        }
+    }
+    blocks
+}
+
+// Pull code out of a response that may mix code fences and commentary
+// Prefers the largest block tagged with one of `tags`, then the largest block of any language
+fn extract_code(response: &str, tags: &[&str]) -> String {
+    let blocks = fenced_blocks(response);
+    let largest = |tagged_only: bool| {
+        blocks
+            .iter()
+            .filter(|(tag, _)| {
+                !tagged_only || tags.iter().any(|wanted| tag.eq_ignore_ascii_case(wanted))
+            })
+            .max_by_key(|(_, body)| body.len())
+            .map(|(_, body)| body.trim().to_string())
+    };
+
+    largest(true)
+        .or_else(|| largest(false))
+        .unwrap_or_else(|| response.trim().to_string())
+}
+
+pub fn extract_rust_code(response: &str) -> String {
+    extract_code(response, &["rust", "rs"])
+}
+
+pub fn extract_html(response: &str) -> String {
+    extract_code(response, &["html"])
+}
+
+// Check that code parses as a complete Rust file
+pub fn validate_rust_file(code: &str) -> Result<(), syn::Error> {
+    syn::parse_file(code).map(|_| ())
+}
+
+// Find the first balanced JSON object or array in a response with extra commentary
+pub fn extract_json_value(response: &str) -> Option<&str> {
+    let start = response.find(['{', '['])?;
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (i, c) in response[start..].char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match c {
+            '"' => in_string = true,
+            '{' | '[' => depth += 1,
+            '}' | ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&response[start..start + i + 1]);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+// Decode a JSON value from a raw LLM response - fences and commentary are ignored
+pub fn decode_llm_json<T: DeserializeOwned>(response: &str) -> Result<T, serde_json::Error> {
+    let unfenced = strip_code_fences(response);
+    match serde_json::from_str(unfenced) {
+        Ok(decoded) => Ok(decoded),
+        Err(e) => match extract_json_value(unfenced).or_else(|| extract_json_value(response)) {
+            Some(json) => serde_json::from_str(json),
+            None => Err(e),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::agents::agent_traits::ProjectScope;
+
+    #[test]
+    fn test_extract_rust_code_from_mixed_response() {
+        let response = "Here is the server:\n```toml\n[dependencies]\n```\n```rust\nfn main() {\n    println!(\"hi\");\n}\n```\nIt prints hi.";
+        let code = extract_rust_code(response);
+        assert_eq!(code, "fn main() {\n    println!(\"hi\");\n}");
+        assert!(validate_rust_file(&code).is_ok());
+
+        // Cut off before the closing fence
+        let code = extract_rust_code("```rust\nfn main() {}\n");
+        assert_eq!(code, "fn main() {}");
+
+        assert!(validate_rust_file("Sure! fn main() {}").is_err());
+    }
+
+    #[test]
+    fn test_extract_json_value() {
+        let response = r#"Sure! Here is the list: ["a}", "b\"]"] and {"ignored": true}"#;
+        assert_eq!(extract_json_value(response), Some(r#"["a}", "b\"]"]"#));
+        assert_eq!(extract_json_value("no json here"), None);
+        assert_eq!(extract_json_value("{ unbalanced"), None);
+    }
+
+    #[test]
+    fn test_decode_llm_json_from_fenced_response() {
+        let response = "Here is the scope:\n```json\n{\n  \"is_crud_required\": true,\n  \"is_user_login_and_logout\": false,\n  \"is_external_urls_required\": false\n}\n```\nLet me know!";
+        let scope: ProjectScope = decode_llm_json(response).expect("Failed to decode");
+        assert!(scope.is_crud_required);
+        assert!(decode_llm_json::<ProjectScope>("not json").is_err());
+    }
+}
@@ -0,0 +1,550 @@
+use crate::models::agents::agent_traits::RouteObject;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use syn::visit::{self, Visit};
+use syn::{
+    Expr, ExprCall, ExprMethodCall, Fields, FnArg, GenericArgument, Item, ItemFn, Lit, Local, Pat,
+    PathArguments, ReturnType, Type,
+};
+
+// Actix attribute macros and route builders, by the HTTP method they stand for
+const METHODS: [&str; 6] = ["get", "post", "put", "delete", "patch", "head"];
+
+// Nested structs deeper than this are left as their type name
+const MAX_DEPTH: usize = 4;
+
+fn last_segment(path: &syn::Path) -> String {
+    path.segments
+        .last()
+        .map(|segment| segment.ident.to_string())
+        .unwrap_or_default()
+}
+
+fn string_literal(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Lit(lit) => match &lit.lit {
+            Lit::Str(s) => Some(s.value()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn join_path(prefix: &str, path: &str) -> String {
+    let joined = format!(
+        "{}/{}",
+        prefix.trim_end_matches('/'),
+        path.trim_start_matches('/')
+    );
+    match joined.len() > 1 {
+        true => joined.trim_end_matches('/').to_string(),
+        false => joined,
+    }
+}
+
+// First type argument, e.g. `Task` of `web::Json<Task>`
+fn generic_argument(segment: &syn::PathSegment) -> Option<&Type> {
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) => args.args.iter().find_map(|arg| match arg {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+// `read_task` style names to `ReadTask`
+fn camel_case(name: &str) -> String {
+    name.split('_')
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+// The structs and functions of a file, looked up while routes are resolved
+struct Module<'a> {
+    structs: HashMap<String, Vec<(String, &'a Type)>>,
+    functions: HashMap<String, &'a ItemFn>,
+}
+
+impl<'a> Module<'a> {
+    fn new(file: &'a syn::File) -> Self {
+        let mut structs = HashMap::new();
+        let mut functions = HashMap::new();
+        for item in &file.items {
+            match item {
+                Item::Struct(item) => {
+                    if let Fields::Named(fields) = &item.fields {
+                        let fields = fields
+                            .named
+                            .iter()
+                            .filter_map(|field| {
+                                field
+                                    .ident
+                                    .as_ref()
+                                    .map(|ident| (ident.to_string(), &field.ty))
+                            })
+                            .collect();
+                        structs.insert(item.ident.to_string(), fields);
+                    }
+                }
+                Item::Fn(item) => {
+                    functions.insert(item.sig.ident.to_string(), item);
+                }
+                _ => {}
+            }
+        }
+        Self { structs, functions }
+    }
+
+    // Schema of a type in the style of `print_rest_api_endpoints`, e.g. `{"id": "number"}`
+    fn shape(&self, ty: &Type, depth: usize) -> Value {
+        let path = match ty {
+            Type::Reference(reference) => return self.shape(&reference.elem, depth),
+            Type::Slice(slice) => return json!([self.shape(&slice.elem, depth)]),
+            Type::Path(path) => &path.path,
+            _ => return json!("not_provided"),
+        };
+        let Some(segment) = path.segments.last() else {
+            return json!("not_provided");
+        };
+        let name = segment.ident.to_string();
+        match name.as_str() {
+            "u8" | "u16" | "u32" | "u64" | "u128" | "usize" | "i8" | "i16" | "i32" | "i64"
+            | "i128" | "isize" | "f32" | "f64" => json!("number"),
+            "String" | "str" => json!("string"),
+            "bool" => json!("bool"),
+            "Vec" | "VecDeque" | "HashSet" | "BTreeSet" => match generic_argument(segment) {
+                Some(item) => json!([self.shape(item, depth)]),
+                None => json!([]),
+            },
+            "HashMap" | "BTreeMap" => json!("object"),
+            "Option" => match generic_argument(segment).map(|inner| self.shape(inner, depth)) {
+                Some(Value::String(inner)) => json!(format!("Option<{}>", inner)),
+                Some(_) => json!("Option<object>"),
+                None => json!("not_provided"),
+            },
+            // Wrappers of the real type
+            "Json" | "Form" | "Query" | "Box" | "Rc" | "Arc" => match generic_argument(segment) {
+                Some(inner) => self.shape(inner, depth),
+                None => json!("not_provided"),
+            },
+            _ => match self.structs.get(&name) {
+                Some(fields) if depth < MAX_DEPTH => Value::Object(
+                    fields
+                        .iter()
+                        .map(|(field, ty)| (field.clone(), self.shape(ty, depth + 1)))
+                        .collect::<Map<String, Value>>(),
+                ),
+                _ => json!(name),
+            },
+        }
+    }
+
+    // Body of a `web::Json` or `web::Form` argument, "None" when the handler takes none
+    fn request_body(&self, handler: &ItemFn) -> Value {
+        handler
+            .sig
+            .inputs
+            .iter()
+            .find_map(|input| {
+                let FnArg::Typed(arg) = input else {
+                    return None;
+                };
+                let Type::Path(path) = arg.ty.as_ref() else {
+                    return None;
+                };
+                let segment = path.path.segments.last()?;
+                matches!(segment.ident.to_string().as_str(), "Json" | "Form")
+                    .then(|| self.shape(&arg.ty, 0))
+            })
+            .unwrap_or_else(|| json!("None"))
+    }
+
+    // What the handler passes to `.json(...)`, or returns as `web::Json<T>`
+    fn response(&self, handler: &ItemFn) -> Value {
+        if let ReturnType::Type(_, ty) = &handler.sig.output {
+            if let Type::Path(path) = ty.as_ref() {
+                if last_segment(&path.path) == "Json" {
+                    return self.shape(ty, 0);
+                }
+            }
+        }
+
+        let mut finder = JsonBodyFinder::default();
+        finder.visit_block(&handler.block);
+        if finder.bodies.is_empty() {
+            return json!("None");
+        }
+        finder
+            .bodies
+            .iter()
+            .find_map(|body| self.expr_shape(body, &finder.locals))
+            .unwrap_or_else(|| json!("not_provided"))
+    }
+
+    fn expr_shape(&self, expr: &Expr, locals: &HashMap<String, Local>) -> Option<Value> {
+        match expr {
+            Expr::Reference(reference) => self.expr_shape(&reference.expr, locals),
+            Expr::Paren(paren) => self.expr_shape(&paren.expr, locals),
+            Expr::Struct(literal) => Some(self.shape(
+                &Type::Path(syn::TypePath {
+                    qself: None,
+                    path: literal.path.clone(),
+                }),
+                0,
+            )),
+            // `x.clone()`, `x.into_inner()` and the like have the type of `x`
+            Expr::MethodCall(call) if call.args.is_empty() => {
+                self.expr_shape(&call.receiver, locals)
+            }
+            Expr::Path(path) => {
+                let name = last_segment(&path.path);
+                if let Some(local) = locals.get(&name) {
+                    if let Pat::Type(typed) = &local.pat {
+                        return Some(self.shape(&typed.ty, 0));
+                    }
+                    if let Some(shape) = local
+                        .init
+                        .as_ref()
+                        .and_then(|init| self.expr_shape(&init.expr, &HashMap::new()))
+                    {
+                        return Some(shape);
+                    }
+                }
+                self.shape_by_name(&name)
+            }
+            _ => None,
+        }
+    }
+
+    // Without types, `task` is taken to be a `Task` and `tasks` a list of them
+    fn shape_by_name(&self, name: &str) -> Option<Value> {
+        let singular = camel_case(name);
+        if self.structs.contains_key(&singular) {
+            return Some(self.shape(&syn::parse_str(&singular).ok()?, 0));
+        }
+        let item = singular.strip_suffix('s')?;
+        if !self.structs.contains_key(item) {
+            return None;
+        }
+        Some(json!([self.shape(&syn::parse_str(item).ok()?, 0)]))
+    }
+
+    fn route_object(&self, method: &str, route: &str, handler: &str) -> RouteObject {
+        let (request_body, response) = match self.functions.get(handler) {
+            Some(handler) => (self.request_body(handler), self.response(handler)),
+            None => (json!("not_provided"), json!("not_provided")),
+        };
+        RouteObject {
+            is_route_dynamic: route.contains('{'),
+            method: method.to_string(),
+            request_body,
+            response,
+            route: route.to_string(),
+        }
+    }
+
+    // `#[get("/path")]` on a handler registered with `.service(handler)`
+    fn attribute_route(&self, handler: &str) -> Option<(String, String)> {
+        let handler = self.functions.get(handler)?;
+        handler.attrs.iter().find_map(|attr| {
+            let method = last_segment(attr.path());
+            if !METHODS.contains(&method.as_str()) {
+                return None;
+            }
+            let path: syn::LitStr = attr
+                .parse_args_with(|input: syn::parse::ParseStream| {
+                    let path: syn::LitStr = input.parse()?;
+                    // Guards and other options after the path are not needed
+                    let _: proc_macro2::TokenStream = input.parse()?;
+                    Ok(path)
+                })
+                .ok()?;
+            Some((method, path.value()))
+        })
+    }
+}
+
+// `.json(...)` arguments and `let` bindings of a handler body
+#[derive(Default)]
+struct JsonBodyFinder {
+    bodies: Vec<Expr>,
+    locals: HashMap<String, Local>,
+}
+
+impl<'ast> Visit<'ast> for JsonBodyFinder {
+    fn visit_expr_method_call(&mut self, call: &'ast ExprMethodCall) {
+        if call.method == "json" && call.args.len() == 1 {
+            self.bodies.push(call.args[0].clone());
+        }
+        visit::visit_expr_method_call(self, call);
+    }
+
+    fn visit_expr_call(&mut self, call: &'ast ExprCall) {
+        // `web::Json(body)` returned from the handler
+        if let Expr::Path(path) = call.func.as_ref() {
+            if last_segment(&path.path) == "Json" && call.args.len() == 1 {
+                self.bodies.push(call.args[0].clone());
+            }
+        }
+        visit::visit_expr_call(self, call);
+    }
+
+    fn visit_local(&mut self, local: &'ast Local) {
+        let ident = match &local.pat {
+            Pat::Ident(ident) => Some(&ident.ident),
+            Pat::Type(typed) => match typed.pat.as_ref() {
+                Pat::Ident(ident) => Some(&ident.ident),
+                _ => None,
+            },
+            _ => None,
+        };
+        if let Some(ident) = ident {
+            self.locals.insert(ident.to_string(), local.clone());
+        }
+        visit::visit_local(self, local);
+    }
+}
+
+// What a builder chain such as `web::scope("/api")` is rooted at
+enum Root {
+    App,
+    Scope(String),
+    Resource(String),
+}
+
+// Route builders such as `web::get().to(handler)` to the method and handler name
+fn route_handler(expr: &Expr) -> Option<(String, String)> {
+    let Expr::MethodCall(call) = expr else {
+        return None;
+    };
+    if call.method != "to" {
+        return None;
+    }
+    let Expr::Call(method) = call.receiver.as_ref() else {
+        return None;
+    };
+    let Expr::Path(method) = method.func.as_ref() else {
+        return None;
+    };
+    let method = last_segment(&method.path);
+    let Some(Expr::Path(handler)) = call.args.first() else {
+        return None;
+    };
+    METHODS
+        .contains(&method.as_str())
+        .then(|| (method, last_segment(&handler.path)))
+}
+
+// Walks actix app builder chains and collects their routes
+struct RouteCollector<'a> {
+    module: &'a Module<'a>,
+    routes: Vec<RouteObject>,
+}
+
+impl RouteCollector<'_> {
+    // Method calls of a builder chain, outermost last, and the call it starts from
+    fn chain(expr: &Expr) -> Option<(Root, Vec<&ExprMethodCall>)> {
+        let mut calls = vec![];
+        let mut current = expr;
+        while let Expr::MethodCall(call) = current {
+            calls.push(call);
+            current = &call.receiver;
+        }
+        calls.reverse();
+
+        let Expr::Call(root) = current else {
+            return None;
+        };
+        let Expr::Path(function) = root.func.as_ref() else {
+            return None;
+        };
+        let segments: Vec<String> = function
+            .path
+            .segments
+            .iter()
+            .map(|segment| segment.ident.to_string())
+            .collect();
+        let root = match segments.as_slice() {
+            [.., app, new] if app == "App" && new == "new" => Root::App,
+            [.., scope] if scope == "scope" => Root::Scope(string_literal(root.args.first()?)?),
+            [.., resource] if resource == "resource" => {
+                Root::Resource(string_literal(root.args.first()?)?)
+            }
+            _ => return None,
+        };
+        Some((root, calls))
+    }
+
+    fn push(&mut self, method: &str, route: &str, handler: &str) {
+        let route_object = self.module.route_object(method, route, handler);
+        if !self.routes.contains(&route_object) {
+            self.routes.push(route_object);
+        }
+    }
+
+    fn collect(&mut self, expr: &Expr, prefix: &str) -> bool {
+        let Some((root, calls)) = Self::chain(expr) else {
+            return false;
+        };
+        let prefix = match &root {
+            Root::App => prefix.to_string(),
+            Root::Scope(path) | Root::Resource(path) => join_path(prefix, path),
+        };
+
+        for call in calls {
+            let args: Vec<&Expr> = call.args.iter().collect();
+            match (call.method.to_string().as_str(), &root, args.as_slice()) {
+                // `.route("/path", web::get().to(handler))` on an app or scope
+                ("route", Root::App | Root::Scope(_), [path, builder]) => {
+                    if let (Some(path), Some((method, handler))) =
+                        (string_literal(path), route_handler(builder))
+                    {
+                        self.push(&method, &join_path(&prefix, &path), &handler);
+                    }
+                }
+                // `web::resource("/path").route(web::get().to(handler))`
+                ("route", Root::Resource(_), [builder]) => {
+                    if let Some((method, handler)) = route_handler(builder) {
+                        self.push(&method, &prefix, &handler);
+                    }
+                }
+                // `web::resource("/path").to(handler)` answers every method, tested with GET
+                ("to", Root::Resource(_), [Expr::Path(handler)]) => {
+                    self.push("get", &prefix, &last_segment(&handler.path));
+                }
+                (method, Root::Resource(_), [Expr::Path(handler)]) if METHODS.contains(&method) => {
+                    self.push(method, &prefix, &last_segment(&handler.path));
+                }
+                // `.service(handler)` with an attribute macro, or a nested scope or resource
+                ("service", _, [service]) => {
+                    if let Expr::Path(handler) = service {
+                        let handler = last_segment(&handler.path);
+                        if let Some((method, path)) = self.module.attribute_route(&handler) {
+                            self.push(&method, &join_path(&prefix, &path), &handler);
+                        }
+                    } else {
+                        self.collect(service, &prefix);
+                    }
+                }
+                _ => {}
+            }
+        }
+        true
+    }
+}
+
+impl<'ast> Visit<'ast> for RouteCollector<'_> {
+    fn visit_expr_method_call(&mut self, call: &'ast ExprMethodCall) {
+        // A chain rooted at `App::new()` is walked once, from its outermost call
+        let is_app = matches!(
+            Self::chain(&Expr::MethodCall(call.clone())),
+            Some((Root::App, _))
+        );
+        if !is_app || !self.collect(&Expr::MethodCall(call.clone()), "") {
+            visit::visit_expr_method_call(self, call);
+        }
+    }
+}
+
+// Routes of an actix-web server with the request and response shapes of their handlers
+pub fn extract_routes(code: &str) -> Result<Vec<RouteObject>, syn::Error> {
+    let file = syn::parse_file(code)?;
+    let module = Module::new(&file);
+    let mut collector = RouteCollector {
+        module: &module,
+        routes: vec![],
+    };
+    collector.visit_file(&file);
+    Ok(collector.routes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_routes_from_code_template() {
+        let template = include_str!("../../../web_template/src/code_template.rs");
+        let routes = extract_routes(template).expect("Template should parse");
+
+        let summary: Vec<(&str, &str)> = routes
+            .iter()
+            .map(|r| (r.method.as_str(), r.route.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("post", "/task"),
+                ("get", "/task"),
+                ("put", "/task"),
+                ("get", "/task/{id}"),
+                ("delete", "/task/{id}"),
+                ("post", "/register"),
+                ("post", "/login"),
+            ]
+        );
+
+        let task = json!({"id": "number", "name": "string", "completed": "bool"});
+        assert_eq!(routes[0].request_body, task);
+        assert_eq!(routes[0].response, json!("None"));
+        assert_eq!(routes[1].response, json!([task]));
+        assert_eq!(routes[3].response, task);
+        assert!(routes[3].is_route_dynamic);
+        assert!(!routes[0].is_route_dynamic);
+    }
+
+    #[test]
+    fn test_extract_scopes_resources_and_attribute_macros() {
+        let code = r#"
+#[derive(Serialize)]
+struct Time { current_time: String, zone: Option<String> }
+
+#[get("/time")]
+async fn time() -> web::Json<Time> { todo!() }
+
+async fn health() -> impl Responder {
+    let status = Time { current_time: String::new(), zone: None };
+    HttpResponse::Ok().json(status)
+}
+
+async fn remove(id: web::Path<u64>) -> impl Responder { HttpResponse::Ok().finish() }
+
+fn main() {
+    HttpServer::new(|| {
+        App::new()
+            .service(
+                web::scope("/api")
+                    .service(time)
+                    .service(web::resource("/items/{id}").route(web::delete().to(remove)))
+            )
+            .route("/health", web::get().to(health))
+    });
+}
+"#;
+        let routes = extract_routes(code).expect("Code should parse");
+        let summary: Vec<(&str, &str)> = routes
+            .iter()
+            .map(|r| (r.method.as_str(), r.route.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("get", "/api/time"),
+                ("delete", "/api/items/{id}"),
+                ("get", "/health"),
+            ]
+        );
+
+        let time = json!({"current_time": "string", "zone": "Option<string>"});
+        assert_eq!(routes[0].response, time);
+        assert_eq!(routes[2].response, time);
+        assert!(routes[1].is_route_dynamic);
+    }
+}
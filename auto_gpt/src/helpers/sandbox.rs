@@ -0,0 +1,800 @@
+use crate::helpers::command_line::PrintCommand;
+use crate::helpers::diagnostics::{parse_cargo_messages, Diagnostic};
+use crate::models::general::error::AutoGptError;
+use serde::Deserialize;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::net::{Ipv4Addr, TcpListener};
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{copy_bidirectional, AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener as AsyncTcpListener, TcpStream, UnixListener, UnixStream};
+use tokio::process::{Child, Command};
+use tokio::task::JoinHandle;
+use tokio::time::{self, Instant};
+
+// Exit status of `timeout` when the wall time ran out
+const TIMEOUT_EXIT_CODE: i32 = 124;
+
+// Outbound HTTP from a server on the host goes to a closed port
+const BLACKHOLE_PROXY: &str = "http://127.0.0.1:9";
+
+// Hidden subcommand of auto_gpt that runs the sandbox end of the relay
+pub const RELAY_COMMAND: &str = "sandbox-relay";
+
+// Socket of the relay in the sandbox's `relay` directory
+const RELAY_SOCKET: &str = "server.sock";
+
+// Sent by the sandbox end once it is connected to the server
+const RELAY_READY: u8 = 1;
+
+// Environment variable telling the generated server which port to bind
+pub const SERVER_PORT_VAR: &str = "PORT";
+
+// Server output kept for the bug report, the start of it is what explains a crash
+const MAX_SERVER_OUTPUT: usize = 16 * 1024;
+
+// How AI written code is isolated from the host
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SandboxMode {
+    // Fails when `bwrap` is not installed, never falls back to the host
+    Bubblewrap,
+    // Only a temp copy of the project and resource limits - no isolation, only when set explicitly
+    Host,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct SandboxConfig {
+    pub mode: SandboxMode,
+    // CPU time of each process, e.g. one rustc invocation
+    pub cpu_secs: u64,
+    // Address space of each process, 0 for no limit
+    pub memory_mb: u64,
+    pub build_timeout_secs: u64,
+    // How long the server may run while its endpoints are tested
+    pub run_timeout_secs: u64,
+    // How long the server may take to accept connections
+    pub startup_timeout_secs: u64,
+    // Build output of sandboxed builds, kept between iterations and never the project's own `target`
+    pub target_dir: PathBuf,
+}
+
+impl Default for SandboxConfig {
+    fn default() -> Self {
+        Self {
+            mode: SandboxMode::Bubblewrap,
+            cpu_secs: 600,
+            memory_mb: 4096,
+            build_timeout_secs: 600,
+            run_timeout_secs: 120,
+            startup_timeout_secs: 60,
+            target_dir: PathBuf::from("runs/target"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct BuildOutput {
+    pub success: bool,
+    pub stderr: String,
+    // Compiler messages, empty when the build did not get to rustc
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+// Output of `cargo test`, compiler messages and test results are both on stdout
+#[derive(Debug)]
+pub struct TestOutput {
+    pub success: bool,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+// Why the server never accepted connections, with what it printed
+#[derive(Debug)]
+pub enum StartupFailure {
+    Exited {
+        status: ExitStatus,
+        output: String,
+    },
+    Timeout {
+        port: u16,
+        secs: u64,
+        output: String,
+    },
+}
+
+impl fmt::Display for StartupFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exited { status, output } => {
+                write!(f, "Server exited during startup with {}\n{}", status, output)
+            }
+            Self::Timeout { port, secs, output } => write!(
+                f,
+                "Server did not accept connections on port {} (from the {} environment variable) within {} seconds\n{}",
+                port, SERVER_PORT_VAR, secs, output
+            ),
+        }
+    }
+}
+
+// Unused port on localhost for the server to bind
+pub fn free_port() -> Result<u16, AutoGptError> {
+    let listener =
+        TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).map_err(AutoGptError::io("free port"))?;
+    let addr = listener
+        .local_addr()
+        .map_err(AutoGptError::io("free port"))?;
+    Ok(addr.port())
+}
+
+// Append a stream to the shared output until it closes
+async fn capture(mut stream: impl AsyncRead + Unpin, output: Arc<Mutex<String>>) {
+    let mut buf = [0; 4096];
+    while let Ok(n) = stream.read(&mut buf).await {
+        if n == 0 {
+            break;
+        }
+        let mut output = output.lock().unwrap_or_else(|e| e.into_inner());
+        if output.len() < MAX_SERVER_OUTPUT {
+            output.push_str(&String::from_utf8_lossy(&buf[..n]));
+        }
+    }
+}
+
+// Sandbox end of the relay, run in the server's network namespace: each connection to the socket
+// is connected to the server's port on the sandbox's loopback
+pub async fn run_relay(socket: PathBuf, port: u16) -> Result<(), AutoGptError> {
+    let _ = fs::remove_file(&socket);
+    let listener =
+        UnixListener::bind(&socket).map_err(AutoGptError::io(socket.display().to_string()))?;
+    loop {
+        let (mut unix, _) = listener
+            .accept()
+            .await
+            .map_err(AutoGptError::io("sandbox relay"))?;
+        tokio::spawn(async move {
+            let Ok(mut tcp) = TcpStream::connect((Ipv4Addr::LOCALHOST, port)).await else {
+                return;
+            };
+            if unix.write_all(&[RELAY_READY]).await.is_ok() {
+                let _ = copy_bidirectional(&mut unix, &mut tcp).await;
+            }
+        });
+    }
+}
+
+// Connection to the server through the relay, fails while the server does not accept connections
+async fn relay_connect(socket: &Path) -> std::io::Result<UnixStream> {
+    let mut unix = UnixStream::connect(socket).await?;
+    let mut ready = [0; 1];
+    unix.read_exact(&mut ready).await?;
+    Ok(unix)
+}
+
+// Host end of the relay: connections to the server's port on the host go through the socket
+async fn forward(listener: AsyncTcpListener, socket: PathBuf) {
+    while let Ok((mut tcp, _)) = listener.accept().await {
+        let socket = socket.clone();
+        tokio::spawn(async move {
+            if let Ok(mut unix) = relay_connect(&socket).await {
+                let _ = copy_bidirectional(&mut tcp, &mut unix).await;
+            }
+        });
+    }
+}
+
+// Host end of the relay of a server in bubblewrap
+#[derive(Debug)]
+struct Relay {
+    socket: PathBuf,
+    task: JoinHandle<()>,
+}
+
+// Server started by `Sandbox::spawn_server`, killed when dropped
+#[derive(Debug)]
+pub struct SandboxedServer {
+    child: Child,
+    port: u16,
+    // stdout and stderr, interleaved as they arrive
+    output: Arc<Mutex<String>>,
+    relay: Option<Relay>,
+}
+
+impl SandboxedServer {
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn output(&self) -> String {
+        self.output
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    // Poll the port until the server accepts connections, fails if it exits or takes too long
+    pub async fn wait_until_ready(&mut self, timeout: Duration) -> Result<(), StartupFailure> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Ok(Some(status)) = self.child.try_wait() {
+                // Let the output readers catch up with a crash
+                time::sleep(Duration::from_millis(100)).await;
+                return Err(StartupFailure::Exited {
+                    status,
+                    output: self.output(),
+                });
+            }
+            let ready = match &self.relay {
+                Some(relay) => relay_connect(&relay.socket).await.is_ok(),
+                None => TcpStream::connect((Ipv4Addr::LOCALHOST, self.port))
+                    .await
+                    .is_ok(),
+            };
+            if ready {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err(StartupFailure::Timeout {
+                    port: self.port,
+                    secs: timeout.as_secs(),
+                    output: self.output(),
+                });
+            }
+            time::sleep(Duration::from_millis(200)).await;
+        }
+    }
+
+    pub async fn stop(mut self) {
+        self.kill_group();
+        let _ = self.child.wait().await;
+    }
+
+    // The server is a grandchild of the spawned process, so its whole process group is killed
+    fn kill_group(&mut self) {
+        if let Some(relay) = &self.relay {
+            relay.task.abort();
+        }
+        if let Some(pid) = self.child.id() {
+            let _ = std::process::Command::new("kill")
+                .args(["-KILL", "--", &format!("-{}", pid)])
+                .status();
+        }
+    }
+}
+
+impl Drop for SandboxedServer {
+    fn drop(&mut self) {
+        self.kill_group();
+    }
+}
+
+fn find_on_path(program: &str) -> Option<PathBuf> {
+    let path = env::var_os("PATH")?;
+    env::split_paths(&path)
+        .map(|dir| dir.join(program))
+        .find(|candidate| candidate.is_file())
+}
+
+fn home_dir_var(name: &str, default: &str) -> Option<PathBuf> {
+    env::var_os(name)
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(default)))
+        .filter(|dir| dir.exists())
+}
+
+// Copy a directory, leaving out the top level entries in `skip`
+fn copy_dir(from: &Path, to: &Path, skip: &[&str]) -> Result<(), AutoGptError> {
+    fs::create_dir_all(to).map_err(AutoGptError::io(to.display().to_string()))?;
+    let entries = fs::read_dir(from).map_err(AutoGptError::io(from.display().to_string()))?;
+    for entry in entries {
+        let entry = entry.map_err(AutoGptError::io(from.display().to_string()))?;
+        let name = entry.file_name();
+        if skip.iter().any(|skipped| name == *skipped) {
+            continue;
+        }
+
+        let (src, dst) = (entry.path(), to.join(&name));
+        if src.is_dir() {
+            copy_dir(&src, &dst, &[])?;
+        } else {
+            fs::copy(&src, &dst).map_err(AutoGptError::io(src.display().to_string()))?;
+        }
+    }
+    Ok(())
+}
+
+// Throwaway copy of the generated project where its code is built and run
+#[derive(Debug)]
+pub struct Sandbox {
+    config: SandboxConfig,
+    bwrap: Option<PathBuf>,
+    // auto_gpt itself, run in bubblewrap as the sandbox end of the relay
+    relay_exe: Option<PathBuf>,
+    // `work` is the project copy, `relay` the relay's socket
+    dir: tempfile::TempDir,
+    // Absolute `config.target_dir`, shared by every sandbox so builds stay incremental
+    target_dir: PathBuf,
+}
+
+impl Sandbox {
+    pub fn new(project_dir: &Path, config: &SandboxConfig) -> Result<Self, AutoGptError> {
+        let bwrap = match config.mode {
+            SandboxMode::Host => {
+                PrintCommand::Issue.print_agent_message(
+                    "Sandbox",
+                    "Sandbox mode is host, generated code runs on the host with resource limits only",
+                );
+                None
+            }
+            SandboxMode::Bubblewrap => Some(find_on_path("bwrap").ok_or_else(|| {
+                AutoGptError::Config(
+                    "bwrap is not installed, install bubblewrap or set the sandbox mode to host to run generated code without isolation"
+                        .to_string(),
+                )
+            })?),
+        };
+
+        let dir = tempfile::Builder::new()
+            .prefix("auto_gpt_sandbox")
+            .tempdir()
+            .map_err(AutoGptError::io("sandbox"))?;
+        let relay_exe = match bwrap {
+            Some(_) => Some(env::current_exe().map_err(AutoGptError::io("current executable"))?),
+            None => None,
+        };
+        fs::create_dir_all(&config.target_dir)
+            .map_err(AutoGptError::io(config.target_dir.display().to_string()))?;
+        let target_dir = config
+            .target_dir
+            .canonicalize()
+            .map_err(AutoGptError::io(config.target_dir.display().to_string()))?;
+        let sandbox = Self {
+            config: config.clone(),
+            bwrap,
+            relay_exe,
+            dir,
+            target_dir,
+        };
+
+        // A target dir inside the project is left out of the copy, like the project's own
+        let project_dir = project_dir
+            .canonicalize()
+            .map_err(AutoGptError::io(project_dir.display().to_string()))?;
+        let mut skip = vec!["target", ".git"];
+        if let Some(nested) = sandbox
+            .target_dir
+            .strip_prefix(&project_dir)
+            .ok()
+            .and_then(|path| path.iter().next())
+            .and_then(|name| name.to_str())
+        {
+            skip.push(nested);
+        }
+        copy_dir(&project_dir, &sandbox.work_dir(), &skip)?;
+        Ok(sandbox)
+    }
+
+    fn work_dir(&self) -> PathBuf {
+        self.dir.path().join("work")
+    }
+
+    fn target_dir(&self) -> &Path {
+        &self.target_dir
+    }
+
+    fn relay_dir(&self) -> PathBuf {
+        self.dir.path().join("relay")
+    }
+
+    // Dependencies are fetched without running any of their code, the error output if that fails
+    async fn fetch(&self) -> Result<Option<String>, AutoGptError> {
+        let fetch = Command::new("cargo")
+            .arg("fetch")
+            .current_dir(self.work_dir())
+            .output()
+            .await
+            .map_err(AutoGptError::io("cargo fetch"))?;
+        match fetch.status.success() {
+            true => Ok(None),
+            false => Ok(Some(String::from_utf8_lossy(&fetch.stderr).to_string())),
+        }
+    }
+
+    // `cargo <args>` with no network under the build timeout, stdout and stderr kept
+    async fn run_offline(&self, cargo_args: &[&str]) -> Result<TestOutput, AutoGptError> {
+        if let Some(stderr) = self.fetch().await? {
+            return Ok(TestOutput {
+                success: false,
+                stdout: String::new(),
+                stderr,
+            });
+        }
+
+        let output = self
+            .command(cargo_args, None, self.config.build_timeout_secs)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output()
+            .await
+            .map_err(AutoGptError::io(format!("cargo {}", cargo_args[0])))?;
+
+        let mut stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        if output.status.code() == Some(TIMEOUT_EXIT_CODE) {
+            stderr.push_str(&format!(
+                "\nKilled after {} seconds",
+                self.config.build_timeout_secs
+            ));
+        }
+        Ok(TestOutput {
+            success: output.status.success(),
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr,
+        })
+    }
+
+    // `cargo build` with no network
+    pub async fn build(&self) -> Result<BuildOutput, AutoGptError> {
+        let output = self
+            .run_offline(&["build", "--offline", "--message-format=json"])
+            .await?;
+        Ok(BuildOutput {
+            success: output.success,
+            diagnostics: parse_cargo_messages(&output.stdout),
+            stderr: output.stderr,
+        })
+    }
+
+    // `cargo test` of one integration test file, every test is run even when some fail
+    pub async fn test(&self, test_name: &str) -> Result<TestOutput, AutoGptError> {
+        self.run_offline(&[
+            "test",
+            "--offline",
+            "--no-fail-fast",
+            "--message-format=json",
+            "--test",
+            test_name,
+        ])
+        .await
+    }
+
+    // Start the built server on `port`, it is killed after `run_timeout_secs`
+    // In bubblewrap the server has a network of its own, only `port` is relayed to the host
+    pub async fn spawn_server(&self, port: u16) -> Result<SandboxedServer, AutoGptError> {
+        let relay = match self.bwrap {
+            Some(_) => {
+                fs::create_dir_all(self.relay_dir())
+                    .map_err(AutoGptError::io(self.relay_dir().display().to_string()))?;
+                let listener = AsyncTcpListener::bind((Ipv4Addr::LOCALHOST, port))
+                    .await
+                    .map_err(AutoGptError::io(format!("port {}", port)))?;
+                let socket = self.relay_dir().join(RELAY_SOCKET);
+                let task = tokio::spawn(forward(listener, socket.clone()));
+                Some(Relay { socket, task })
+            }
+            None => None,
+        };
+
+        let mut child = self
+            .command(
+                &["run", "--offline"],
+                Some(port),
+                self.config.run_timeout_secs,
+            )
+            .env(SERVER_PORT_VAR, port.to_string())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .process_group(0)
+            .spawn()
+            .map_err(AutoGptError::io("cargo run"))?;
+
+        let output = Arc::new(Mutex::new(String::new()));
+        if let Some(stdout) = child.stdout.take() {
+            tokio::spawn(capture(stdout, output.clone()));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            tokio::spawn(capture(stderr, output.clone()));
+        }
+        Ok(SandboxedServer {
+            child,
+            port,
+            output,
+            relay,
+        })
+    }
+
+    // `cargo <args>` under resource limits, inside bubblewrap unless the mode is host
+    // A server in bubblewrap only has loopback, the relay next to it makes its port reachable
+    fn command(&self, cargo_args: &[&str], port: Option<u16>, timeout_secs: u64) -> Command {
+        let serve = port.is_some();
+        let mut script = vec![format!("ulimit -t {}", self.config.cpu_secs)];
+        if self.config.memory_mb > 0 {
+            script.push(format!("ulimit -v {}", self.config.memory_mb * 1024));
+        }
+        if let (Some(_), Some(port)) = (&self.bwrap, port) {
+            script.push(format!(
+                "/relay-bin/auto_gpt {} /relay/{} {} &",
+                RELAY_COMMAND, RELAY_SOCKET, port
+            ));
+        }
+        script.push(format!("exec timeout -k 5 {} \"$@\"", timeout_secs));
+        let script = script.join("\n");
+
+        let mut command = match &self.bwrap {
+            Some(bwrap) => {
+                let mut command = Command::new(bwrap);
+                command.args(self.bwrap_args(serve));
+                command.args(["sh", "-c", &script, "sandbox", "cargo"]);
+                command
+            }
+            None => {
+                let mut command = Command::new("sh");
+                command
+                    .args(["-c", &script, "sandbox", "cargo"])
+                    .current_dir(self.work_dir())
+                    .env("CARGO_TARGET_DIR", self.target_dir());
+                command
+            }
+        };
+        command.args(cargo_args);
+
+        if serve {
+            for proxy in [
+                "HTTP_PROXY",
+                "HTTPS_PROXY",
+                "ALL_PROXY",
+                "http_proxy",
+                "https_proxy",
+                "all_proxy",
+            ] {
+                command.env(proxy, BLACKHOLE_PROXY);
+            }
+            command.env("NO_PROXY", "localhost,127.0.0.1");
+            command.env("no_proxy", "localhost,127.0.0.1");
+        }
+        command
+    }
+
+    // Read only system and toolchain, writable project copy and target dir, nothing else of the host
+    fn bwrap_args(&self, serve: bool) -> Vec<String> {
+        let mut args: Vec<String> = vec![
+            "--die-with-parent",
+            "--new-session",
+            "--unshare-all",
+            "--ro-bind",
+            "/usr",
+            "/usr",
+            "--ro-bind-try",
+            "/bin",
+            "/bin",
+            "--ro-bind-try",
+            "/lib",
+            "/lib",
+            "--ro-bind-try",
+            "/lib64",
+            "/lib64",
+            "--ro-bind-try",
+            "/etc/ssl",
+            "/etc/ssl",
+            "--ro-bind-try",
+            "/etc/resolv.conf",
+            "/etc/resolv.conf",
+            "--proc",
+            "/proc",
+            "--dev",
+            "/dev",
+            "--tmpfs",
+            "/tmp",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+
+        let mut bind = |flag: &str, from: &Path, to: &str| {
+            args.extend([flag.to_string(), from.display().to_string(), to.to_string()]);
+        };
+
+        // Builds and the server only have loopback, the server's port goes out through the relay
+        if let (true, Some(relay_exe)) = (serve, &self.relay_exe) {
+            bind("--ro-bind", relay_exe, "/relay-bin/auto_gpt");
+            bind("--bind", &self.relay_dir(), "/relay");
+        }
+
+        // Toolchain and downloaded crates are read only, cargo's lock files go to a tmpfs home
+        let mut path = "/usr/bin:/bin".to_string();
+        if let Some(cargo_home) = home_dir_var("CARGO_HOME", ".cargo") {
+            bind("--ro-bind-try", &cargo_home.join("bin"), "/cargo/bin");
+            bind(
+                "--ro-bind-try",
+                &cargo_home.join("registry"),
+                "/cargo/registry",
+            );
+            bind("--ro-bind-try", &cargo_home.join("git"), "/cargo/git");
+            path = format!("/cargo/bin:{}", path);
+        }
+        if let Some(rustup_home) = home_dir_var("RUSTUP_HOME", ".rustup") {
+            bind("--ro-bind", &rustup_home, "/rustup");
+        }
+        bind("--bind", &self.work_dir(), "/work");
+        bind("--bind", self.target_dir(), "/target");
+
+        args.extend(
+            [
+                "--setenv",
+                "CARGO_HOME",
+                "/cargo",
+                "--setenv",
+                "RUSTUP_HOME",
+                "/rustup",
+                "--setenv",
+                "CARGO_TARGET_DIR",
+                "/target",
+                "--setenv",
+                "HOME",
+                "/tmp",
+                "--setenv",
+                "PATH",
+                &path,
+                "--chdir",
+                "/work",
+            ]
+            .map(String::from),
+        );
+        args
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::config::scratch_workspace;
+
+    #[tokio::test]
+    async fn test_host_sandbox_builds_a_copy() {
+        let (_project, config) = scratch_workspace();
+        fs::write(config.exec_main_path(), "fn main() {}\n").unwrap();
+
+        let sandbox = Sandbox::new(&config.project_dir, &config.sandbox).expect("No sandbox");
+        let build = sandbox.build().await.expect("Failed to run build");
+        assert!(build.success, "{}", build.stderr);
+        assert!(sandbox.work_dir().join("src/main.rs").exists());
+
+        // Code is built from the copy, not the project
+        fs::write(sandbox.work_dir().join("src/main.rs"), "fn main() {").unwrap();
+        let broken = sandbox.build().await.expect("Failed to run build");
+        assert!(!broken.success);
+        assert!(broken.diagnostics.iter().any(|d| d.level == "error"));
+
+        // Build output goes to the sandbox target dir, which the next sandbox reuses
+        assert!(config.sandbox.target_dir.join("debug").exists());
+        assert!(!config.project_dir.join("target").exists());
+        let next = Sandbox::new(&config.project_dir, &config.sandbox).expect("No sandbox");
+        assert_eq!(next.target_dir(), sandbox.target_dir());
+        assert!(!next.work_dir().join("sandbox_target").exists());
+    }
+
+    #[tokio::test]
+    async fn test_server_readiness_and_crash() {
+        let (_project, config) = scratch_workspace();
+        let sandbox_config = config.sandbox.clone();
+        let timeout = Duration::from_secs(30);
+
+        let sandbox = Sandbox::new(&config.project_dir, &sandbox_config).expect("No sandbox");
+        assert!(sandbox.build().await.expect("Failed to run build").success);
+        let port = free_port().expect("No free port");
+        let mut running = sandbox
+            .spawn_server(port)
+            .await
+            .expect("Failed to start server");
+        running
+            .wait_until_ready(timeout)
+            .await
+            .expect("Server should be ready");
+        running.stop().await;
+
+        fs::write(
+            config.exec_main_path(),
+            "fn main() { panic!(\"no database\") }\n",
+        )
+        .unwrap();
+        let sandbox = Sandbox::new(&config.project_dir, &sandbox_config).expect("No sandbox");
+        assert!(sandbox.build().await.expect("Failed to run build").success);
+        let mut crashing = sandbox
+            .spawn_server(port)
+            .await
+            .expect("Failed to start server");
+        match crashing.wait_until_ready(timeout).await {
+            Err(StartupFailure::Exited { output, .. }) => assert!(output.contains("no database")),
+            other => panic!("Server should have crashed: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_relay_forwards_one_port() {
+        let dir = tempfile::tempdir().unwrap();
+        let echo = AsyncTcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .await
+            .unwrap();
+        let echo_port = echo.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = echo.accept().await {
+                tokio::spawn(async move {
+                    let (mut reader, mut writer) = stream.split();
+                    let _ = tokio::io::copy(&mut reader, &mut writer).await;
+                });
+            }
+        });
+
+        // Nothing listens on the port of the second relay
+        let socket = dir.path().join("echo.sock");
+        let closed_socket = dir.path().join("closed.sock");
+        tokio::spawn(run_relay(socket.clone(), echo_port));
+        tokio::spawn(run_relay(closed_socket.clone(), free_port().unwrap()));
+        time::sleep(Duration::from_millis(200)).await;
+        assert!(relay_connect(&closed_socket).await.is_err());
+
+        let host = AsyncTcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .await
+            .unwrap();
+        let host_port = host.local_addr().unwrap().port();
+        tokio::spawn(forward(host, socket));
+        let mut client = TcpStream::connect((Ipv4Addr::LOCALHOST, host_port))
+            .await
+            .unwrap();
+        client.write_all(b"ping").await.unwrap();
+        let mut reply = [0; 4];
+        client.read_exact(&mut reply).await.unwrap();
+        assert_eq!(&reply, b"ping");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs bubblewrap, run with --ignored where bwrap is installed"]
+    async fn test_bubblewrap_server_has_no_outbound_network() {
+        let (_project, config) = scratch_workspace();
+        let host = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let host_port = host.local_addr().unwrap().port();
+        let main = format!(
+            r#"fn main() {{
+    for addr in ["127.0.0.1:{}", "1.1.1.1:443"] {{
+        let connected = std::net::TcpStream::connect_timeout(
+            &addr.parse().unwrap(),
+            std::time::Duration::from_secs(3),
+        )
+        .is_ok();
+        println!("{{}} connected: {{}}", addr, connected);
+    }}
+}}
+"#,
+            host_port
+        );
+        fs::write(config.exec_main_path(), main).unwrap();
+
+        let sandbox_config = SandboxConfig {
+            mode: SandboxMode::Bubblewrap,
+            ..config.sandbox.clone()
+        };
+        let sandbox = Sandbox::new(&config.project_dir, &sandbox_config).expect("No sandbox");
+        let build = sandbox.build().await.expect("Failed to run build");
+        assert!(build.success, "{}", build.stderr);
+        let mut server = sandbox
+            .spawn_server(free_port().unwrap())
+            .await
+            .expect("Failed to start server");
+        match server.wait_until_ready(Duration::from_secs(30)).await {
+            Err(StartupFailure::Exited { output, .. }) => {
+                assert!(output.contains(&format!("127.0.0.1:{} connected: false", host_port)));
+                assert!(
+                    output.contains("1.1.1.1:443 connected: false"),
+                    "{}",
+                    output
+                );
+            }
+            other => panic!("Server should have exited: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use std::fmt;
+
+// Integration tests of the generated project, as `cargo test --test` names them
+pub const INTEGRATION_TESTS: &str = "api";
+
+// One test of a `cargo test` run
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestCase {
+    pub name: String,
+    pub passed: bool,
+    // What the test printed, only kept for failures
+    pub output: String,
+}
+
+impl fmt::Display for TestCase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.passed {
+            true => write!(f, "PASS {}", self.name),
+            false => write!(f, "FAIL {}\n{}", self.name, self.output.trim_end()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TestReport {
+    pub tests: Vec<TestCase>,
+}
+
+impl TestReport {
+    pub fn failures(&self) -> Vec<&TestCase> {
+        self.tests.iter().filter(|test| !test.passed).collect()
+    }
+}
+
+impl fmt::Display for TestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for test in &self.tests {
+            writeln!(f, "{}", test)?;
+        }
+        let failed = self.failures().len();
+        write!(f, "{} passed, {} failed", self.tests.len() - failed, failed)
+    }
+}
+
+// Results from the stdout of `cargo test`, lines that are not test output are skipped
+pub fn parse_test_output(stdout: &str) -> TestReport {
+    let mut tests: Vec<TestCase> = vec![];
+    // Test whose captured output is being read
+    let mut section: Option<usize> = None;
+
+    for line in stdout.lines() {
+        if let Some(result) = line.strip_prefix("test ") {
+            let outcome = result
+                .strip_suffix(" ... ok")
+                .map(|name| (name, true))
+                .or_else(|| result.strip_suffix(" ... FAILED").map(|name| (name, false)));
+            if let Some((name, passed)) = outcome {
+                tests.push(TestCase {
+                    name: name.to_string(),
+                    passed,
+                    output: String::new(),
+                });
+                section = None;
+                continue;
+            }
+        }
+
+        // `---- name stdout ----` starts the output of a failed test
+        if let Some(name) = line
+            .strip_prefix("---- ")
+            .and_then(|rest| rest.strip_suffix(" stdout ----"))
+        {
+            section = tests.iter().position(|test| test.name == name);
+            continue;
+        }
+        if line == "failures:" || line.starts_with("test result:") {
+            section = None;
+            continue;
+        }
+        if let Some(index) = section {
+            tests[index].output.push_str(line);
+            tests[index].output.push('\n');
+        }
+    }
+    TestReport { tests }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_test_output() {
+        let stdout = r#"{"reason":"build-finished","success":true}
+
+running 3 tests
+test test_get_tasks ... ok
+test test_create_task ... FAILED
+test test_delete_task ... ignored
+
+failures:
+
+---- test_create_task stdout ----
+
+thread 'test_create_task' panicked at tests/api.rs:20:5:
+assertion `left == right` failed
+  left: 404
+ right: 200
+
+failures:
+    test_create_task
+
+test result: FAILED. 1 passed; 1 failed; 1 ignored; 0 measured; 0 filtered out
+"#;
+        let report = parse_test_output(stdout);
+        assert_eq!(report.tests.len(), 2);
+        assert!(report.tests[0].passed);
+
+        let failures = report.failures();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].name, "test_create_task");
+        assert!(failures[0].output.contains("left: 404"));
+        assert!(!failures[0].output.contains("test result"));
+        assert!(report.to_string().ends_with("1 passed, 1 failed"));
+    }
+}
@@ -11,9 +11,151 @@ mod apis;
 mod helpers;
 mod models;
 
-use helpers::command_line::get_user_response;
+use apis::llm_provider::llm_provider;
+use clap::{Parser, Subcommand};
+use helpers::command_line::{get_user_response, PrintCommand};
+use helpers::config::{set_workspace_config, workspace_config, ConfigArgs, WorkspaceConfig};
+use helpers::general::save_backend_code;
+use helpers::sandbox::{run_relay, RELAY_COMMAND};
+use models::agent_manager::managing_agent::ManagingAgent;
+use models::agent_manager::run_checkpoint::{new_run_dir, read_factsheet, write_factsheet};
+use models::agents::agent_traits::FactSheet;
+use models::general::error::AutoGptError;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 
-fn main() {
-    let usr_req = get_user_response("What web server are we building today?");
-    dbg!(usr_req);
+#[derive(Debug, Parser)]
+#[command(about = "Agents that write, build and test a web server from a prompt")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+
+    #[command(flatten)]
+    config: ConfigArgs,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Build a web server from a request, asks for one if not given
+    Build { request: Option<String> },
+    /// Continue a checkpointed run directory, or start again from a saved factsheet
+    Resume { path: PathBuf },
+    /// Build and test the code already in the output directory
+    TestOnly {
+        /// Factsheet of the project, if there is one
+        #[arg(long)]
+        factsheet: Option<PathBuf>,
+    },
+    /// Restore the backend code of an earlier iteration into the output directory
+    Rollback {
+        /// Run directory or factsheet file
+        path: PathBuf,
+        /// Iteration to restore, as listed in the factsheet's code_snapshots
+        iteration: usize,
+    },
+    /// Relay connections to a server in the sandbox, started by the sandbox itself
+    #[command(name = RELAY_COMMAND, hide = true)]
+    SandboxRelay { socket: PathBuf, port: u16 },
+}
+
+// Put an earlier version of the code back, `test-only` can then build and test it
+fn roll_back(path: &Path, iteration: usize) -> Result<(), AutoGptError> {
+    let mut factsheet = read_factsheet(path)?;
+    let code = factsheet.roll_back_to(iteration).ok_or_else(|| {
+        AutoGptError::Config(format!("No code snapshot for iteration {}", iteration))
+    })?;
+
+    save_backend_code(&code)?;
+    write_factsheet(path, &factsheet)?;
+    PrintCommand::AICall.print_agent_message(
+        "Project manager",
+        format!(
+            "Restored iteration {} as iteration {}",
+            iteration,
+            factsheet.code_snapshots.len()
+        )
+        .as_str(),
+    );
+    Ok(())
+}
+
+async fn run(command: Command) -> Result<(), AutoGptError> {
+    let config = workspace_config()?;
+    if let Command::Rollback { path, iteration } = &command {
+        return roll_back(path, *iteration);
+    }
+    let provider = llm_provider()?;
+    PrintCommand::AICall.print_agent_message(
+        "Project manager",
+        format!("Using LLM provider: {}", provider.name()).as_str(),
+    );
+
+    let mut managing_agent = match &command {
+        Command::Build { request } => {
+            let usr_req = match request {
+                Some(request) => request.clone(),
+                None if config.non_interactive => {
+                    return Err(AutoGptError::Config(
+                        "A request is required in non-interactive mode".to_string(),
+                    ))
+                }
+                None => get_user_response("What web server are we building today?"),
+            };
+            ManagingAgent::new(usr_req)
+                .await?
+                .with_run_dir(new_run_dir(&config.runs_dir))
+        }
+        Command::Resume { path } if path.is_dir() => ManagingAgent::resume(path)?,
+        Command::Resume { path } => ManagingAgent::from_factsheet(read_factsheet(path)?)
+            .with_run_dir(new_run_dir(&config.runs_dir)),
+        Command::TestOnly { factsheet } => {
+            let factsheet = match factsheet {
+                Some(path) => read_factsheet(path)?,
+                None => FactSheet::new(String::new()),
+            };
+            ManagingAgent::from_factsheet(factsheet)
+        }
+        Command::Rollback { .. } | Command::SandboxRelay { .. } => {
+            unreachable!("Rollback and the relay do not run the agents")
+        }
+    };
+
+    if let Some(run_dir) = managing_agent.run_dir() {
+        PrintCommand::AICall.print_agent_message(
+            "Project manager",
+            format!("Checkpointing run to {}", run_dir.display()).as_str(),
+        );
+    }
+
+    match command {
+        Command::TestOnly { .. } => managing_agent.test_project().await,
+        _ => managing_agent.execute_project().await,
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    if let Command::SandboxRelay { socket, port } = cli.command {
+        return match run_relay(socket, port).await {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("{}", e);
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    let res = match WorkspaceConfig::load(&cli.config).and_then(set_workspace_config) {
+        Ok(()) => run(cli.command).await,
+        Err(e) => Err(e),
+    };
+
+    match res {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            PrintCommand::Issue.print_agent_message("Project manager", e.to_string().as_str());
+            ExitCode::FAILURE
+        }
+    }
 }
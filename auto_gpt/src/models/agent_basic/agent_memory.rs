@@ -0,0 +1,174 @@
+use crate::models::general::llm::Message;
+use serde::Deserialize;
+
+// Roughly how many characters make up a token for English text and code
+const CHARS_PER_TOKEN: usize = 4;
+
+// Longest excerpt of each earlier message kept in a summary
+const SUMMARY_EXCERPT_CHARS: usize = 160;
+
+// How much of an agent's earlier calls is sent along with a new call
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MemoryMode {
+    // Every call is a single stateless message
+    Off,
+    // The most recent messages, verbatim
+    Window,
+    // The most recent messages, plus a short digest of everything before them
+    Summary,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct MemoryConfig {
+    pub mode: MemoryMode,
+    // Number of recent messages sent verbatim - a prompt and its response are two messages
+    pub window: usize,
+    // Upper bound on the estimated tokens of the history sent with a call
+    pub token_budget: usize,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            mode: MemoryMode::Window,
+            window: 6,
+            token_budget: 3000,
+        }
+    }
+}
+
+// Cheap token estimate, good enough for budgeting
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+// Keep the start of the text, where prompts and compiler errors put the important part
+fn truncate_to_tokens(text: &str, max_tokens: usize) -> String {
+    if estimate_tokens(text) <= max_tokens {
+        return text.to_string();
+    }
+    let marker = "... [truncated]";
+    let keep = (max_tokens * CHARS_PER_TOKEN).saturating_sub(marker.len());
+    let head: String = text.chars().take(keep).collect();
+    format!("{}{}", head, marker)
+}
+
+fn summarise(messages: &[Message]) -> String {
+    let lines: Vec<String> = messages
+        .iter()
+        .map(|message| {
+            let excerpt: String = message
+                .content
+                .split_whitespace()
+                .collect::<Vec<&str>>()
+                .join(" ")
+                .chars()
+                .take(SUMMARY_EXCERPT_CHARS)
+                .collect();
+            format!("- {}: {}", message.role, excerpt)
+        })
+        .collect();
+    format!("SUMMARY OF EARLIER CALLS:\n{}", lines.join("\n"))
+}
+
+// Messages from memory to send before the next prompt, oldest first
+// The newest messages win when the token budget runs out
+pub fn recall(memory: &[Message], config: &MemoryConfig) -> Vec<Message> {
+    if config.mode == MemoryMode::Off {
+        return vec![];
+    }
+
+    let (older, recent) = memory.split_at(memory.len().saturating_sub(config.window));
+    let mut budget = config.token_budget;
+    let mut history: Vec<Message> = vec![];
+    for message in recent.iter().rev() {
+        if budget == 0 {
+            break;
+        }
+        let content = truncate_to_tokens(&message.content, budget);
+        budget = budget.saturating_sub(estimate_tokens(&content));
+        history.push(Message {
+            role: message.role.clone(),
+            content,
+        });
+    }
+
+    if config.mode == MemoryMode::Summary && !older.is_empty() && budget > 0 {
+        history.push(Message {
+            role: "system".to_string(),
+            content: truncate_to_tokens(&summarise(older), budget),
+        });
+    }
+
+    history.reverse();
+    history
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: &str, content: &str) -> Message {
+        Message {
+            role: role.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_recall_window_and_summary() {
+        let memory = vec![
+            message("user", "first prompt"),
+            message("assistant", "first response"),
+            message("user", "second prompt"),
+            message("assistant", "second response"),
+        ];
+        let window = MemoryConfig {
+            window: 2,
+            ..MemoryConfig::default()
+        };
+
+        let recalled = recall(&memory, &window);
+        assert_eq!(recalled.len(), 2);
+        assert_eq!(recalled[0].content, "second prompt");
+
+        let summary = recall(
+            &memory,
+            &MemoryConfig {
+                mode: MemoryMode::Summary,
+                ..window.clone()
+            },
+        );
+        assert_eq!(summary.len(), 3);
+        assert_eq!(summary[0].role, "system");
+        assert!(summary[0].content.contains("- assistant: first response"));
+
+        let off = MemoryConfig {
+            mode: MemoryMode::Off,
+            ..window
+        };
+        assert!(recall(&memory, &off).is_empty());
+    }
+
+    #[test]
+    fn test_recall_respects_token_budget() {
+        let memory = vec![
+            message("user", &"old broken code ".repeat(200)),
+            message("assistant", &"fixed code ".repeat(200)),
+        ];
+        let config = MemoryConfig {
+            token_budget: 100,
+            ..MemoryConfig::default()
+        };
+
+        let recalled = recall(&memory, &config);
+        let tokens: usize = recalled.iter().map(|m| estimate_tokens(&m.content)).sum();
+        assert!(tokens <= 100);
+        // Newest message is kept first
+        assert_eq!(recalled.len(), 1);
+        assert_eq!(recalled[0].role, "assistant");
+        assert!(recalled[0].content.ends_with("[truncated]"));
+    }
+}
@@ -1,7 +1,9 @@
+use crate::models::agent_basic::agent_memory::{recall, MemoryConfig};
 use crate::models::agent_basic::basic_traits::BasicTraits;
 use crate::models::general::llm::Message;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub enum AgentState {
     Discovery,
     Working,
@@ -9,6 +11,8 @@ pub enum AgentState {
     Finished,
 }
 
+// Not every field is read by every agent yet
+#[allow(dead_code)]
 #[derive(Debug)]
 pub struct BasicAgent {
     pub objective: String,
@@ -17,6 +21,25 @@ pub struct BasicAgent {
     pub memory: Vec<Message>,
 }
 
+impl BasicAgent {
+    // Record a prompt and the response the LLM gave to it
+    pub fn remember(&mut self, prompt: String, response: String) {
+        self.memory.push(Message {
+            role: "user".to_string(),
+            content: prompt,
+        });
+        self.memory.push(Message {
+            role: "assistant".to_string(),
+            content: response,
+        });
+    }
+
+    // History to send with the next call, see `MemoryConfig`
+    pub fn recall(&self, config: &MemoryConfig) -> Vec<Message> {
+        recall(&self.memory, config)
+    }
+}
+
 impl BasicTraits for BasicAgent {
     fn new(objective: String, position: String) -> Self {
         Self {
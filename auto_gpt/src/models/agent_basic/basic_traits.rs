@@ -1,5 +1,7 @@
 use crate::models::{agent_basic::basic_agent::AgentState, general::llm::Message};
 
+// Not every accessor is used by every agent yet
+#[allow(dead_code)]
 pub trait BasicTraits {
     fn new(objective: String, position: String) -> Self;
     fn update_state(&mut self, new_state: AgentState);
@@ -1,2 +1,3 @@
+pub mod agent_memory;
 pub mod basic_agent;
 pub mod basic_traits;
@@ -0,0 +1,60 @@
+use crate::models::agents::agent_architect::AgentSolutionArchitect;
+use crate::models::agents::agent_backend::AgentBackendDeveloper;
+use crate::models::agents::agent_database::AgentDatabaseDesigner;
+use crate::models::agents::agent_frontend::AgentFrontendDeveloper;
+use crate::models::agents::agent_qa::AgentQATester;
+use crate::models::agents::agent_traits::SpecialFunctions;
+use crate::models::general::error::AutoGptError;
+
+// Names usable in the `pipeline` setting, in their default order
+pub const DEFAULT_PIPELINE: [&str; 5] = ["architect", "database", "backend", "qa", "frontend"];
+
+// Agent that fixes what later agents report in `FactSheet::test_failures`
+pub const BACKEND_AGENT: &str = "backend";
+
+// Create an agent from its pipeline name
+pub fn create_agent(name: &str) -> Result<Box<dyn SpecialFunctions>, AutoGptError> {
+    match name.trim().to_lowercase().as_str() {
+        "architect" => Ok(Box::new(AgentSolutionArchitect::new())),
+        "database" => Ok(Box::new(AgentDatabaseDesigner::new())),
+        "backend" => Ok(Box::new(AgentBackendDeveloper::new())),
+        "qa" => Ok(Box::new(AgentQATester::new())),
+        "frontend" => Ok(Box::new(AgentFrontendDeveloper::new())),
+        other => Err(AutoGptError::Config(format!(
+            "Unknown agent in pipeline: {}",
+            other
+        ))),
+    }
+}
+
+// Create every agent of a pipeline, in order
+pub fn create_pipeline(names: &[String]) -> Result<Vec<Box<dyn SpecialFunctions>>, AutoGptError> {
+    names.iter().map(|name| create_agent(name)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_pipeline() {
+        let names: Vec<String> = DEFAULT_PIPELINE.iter().map(|s| s.to_string()).collect();
+        let agents = create_pipeline(&names).expect("Failed to create pipeline");
+        let positions: Vec<&String> = agents
+            .iter()
+            .map(|agent| &agent.get_attributes_from_agent().position)
+            .collect();
+        assert_eq!(
+            positions,
+            vec![
+                "Solutions Architect",
+                "Database Designer",
+                "Backend Developer",
+                "QA Tester",
+                "Frontend Developer"
+            ]
+        );
+
+        assert!(create_agent("designer").is_err());
+    }
+}
@@ -2,85 +2,380 @@ use crate::models::agent_basic::basic_agent::{AgentState, BasicAgent};
 use crate::models::agents::agent_traits::{FactSheet, SpecialFunctions};
 
 use crate::ai_functions::aifunc_managing::convert_user_input_to_goal;
+use crate::apis::usage::usage_ledger;
+use crate::helpers::command_line::PrintCommand;
+use crate::helpers::config::workspace_config;
 use crate::helpers::general::ai_task_request;
-use crate::models::agents::agent_architect::AgentSolutionArchitect;
+use crate::models::agent_basic::basic_traits::BasicTraits;
+use crate::models::agent_manager::agent_registry::{create_pipeline, BACKEND_AGENT};
+use crate::models::agent_manager::run_checkpoint::{AgentCheckpoint, RunCheckpoint};
+use crate::models::agents::agent_backend::AgentBackendDeveloper;
+use crate::models::general::error::AutoGptError;
+use std::path::{Path, PathBuf};
+
+const MANAGER_POSITION: &str = "Project manager";
 
 #[derive(Debug)]
 pub struct ManagingAgent {
-    _attributes: BasicAgent,
+    attributes: BasicAgent,
     factsheet: FactSheet,
     agents: Vec<Box<dyn SpecialFunctions>>,
+    // Agent names, with the state each one starts from
+    pipeline: Vec<AgentCheckpoint>,
+    // Where the factsheet and agent states are checkpointed, if anywhere
+    run_dir: Option<PathBuf>,
 }
 
 impl ManagingAgent {
-    pub async fn new(usr_req: String) -> Result<Self, Box<dyn std::error::Error>> {
-        let position = "Project manager".to_string();
+    pub async fn new(usr_req: String) -> Result<Self, AutoGptError> {
+        let mut managing_agent = Self::from_factsheet(FactSheet::new(String::new()));
+        managing_agent.factsheet.project_description = ai_task_request(
+            usr_req,
+            &mut managing_agent.attributes,
+            get_function_string!(convert_user_input_to_goal),
+            convert_user_input_to_goal,
+        )
+        .await?;
+
+        Ok(managing_agent)
+    }
+
+    // Continue a project from an existing factsheet, without asking the LLM for a goal
+    pub fn from_factsheet(factsheet: FactSheet) -> Self {
         let attributes = BasicAgent {
             objective: "Manage agents who are buidling an excellent website for the user"
                 .to_string(),
-            position: position.clone(),
+            position: MANAGER_POSITION.to_string(),
             state: AgentState::Discovery,
             memory: vec![],
         };
 
-        let project_description = ai_task_request(
-            usr_req,
-            &position,
-            get_function_string!(convert_user_input_to_goal),
-            convert_user_input_to_goal,
-        )
-        .await;
-
         let agents: Vec<Box<dyn SpecialFunctions>> = vec![];
 
-        let mut factsheet = FactSheet {
-            project_description,
-            project_scope: None,
-            external_urls: None,
-            backend_code: None,
-            api_endpoint_schema: None,
-        };
-
-        // Return
-        Ok(Self {
-            _attributes: attributes,
+        Self {
+            attributes,
             factsheet,
             agents,
-        })
+            pipeline: vec![],
+            run_dir: None,
+        }
+    }
+
+    // Checkpoint the run into `run_dir` after every state transition
+    pub fn with_run_dir(mut self, run_dir: PathBuf) -> Self {
+        self.run_dir = Some(run_dir);
+        self
+    }
+
+    // Continue a checkpointed run from the state each agent reached
+    pub fn resume(run_dir: &Path) -> Result<Self, AutoGptError> {
+        let (factsheet, checkpoint) = RunCheckpoint::load(run_dir)?;
+        let mut managing_agent =
+            Self::from_factsheet(factsheet).with_run_dir(run_dir.to_path_buf());
+        if let Some(checkpoint) = checkpoint {
+            managing_agent.pipeline = checkpoint.agents;
+            usage_ledger().extend(checkpoint.usage);
+        }
+        Ok(managing_agent)
+    }
+
+    pub fn run_dir(&self) -> Option<&Path> {
+        self.run_dir.as_deref()
     }
 
     fn add_agent(&mut self, agent: Box<dyn SpecialFunctions>) {
         self.agents.push(agent);
     }
 
-    fn create_agent(&mut self) {
-        self.add_agent(Box::new(AgentSolutionArchitect::new()));
-        // add other agents later
+    // Agents run in the order of the `pipeline` setting, or of the resumed checkpoint
+    fn create_agent(&mut self) -> Result<(), AutoGptError> {
+        if self.pipeline.is_empty() {
+            self.pipeline = workspace_config()?
+                .pipeline
+                .into_iter()
+                .map(|name| AgentCheckpoint {
+                    name,
+                    state: AgentState::Discovery,
+                    progress: None,
+                })
+                .collect();
+        }
+
+        let names: Vec<String> = self.pipeline.iter().map(|a| a.name.clone()).collect();
+        self.agents.clear();
+        for (mut agent, saved) in create_pipeline(&names)?
+            .into_iter()
+            .zip(self.pipeline.clone())
+        {
+            agent
+                .get_attributes_from_agent_mut()
+                .update_state(saved.state);
+            if let Some(progress) = saved.progress {
+                agent.restore_progress(progress)?;
+            }
+            self.add_agent(agent);
+        }
+        Ok(())
     }
 
-    pub async fn execute_project(&mut self) {
-        self.create_agent();
-        for agent in &mut self.agents {
-            let agent_res = agent.execute(&mut self.factsheet).await;
-            let agent_info = agent.get_attributes_from_agent();
-            dbg!(agent_info);
+    // Save the factsheet and every agent's state to the run directory
+    fn checkpoint(&mut self) -> Result<(), AutoGptError> {
+        for (checkpoint, agent) in self.pipeline.iter_mut().zip(&self.agents) {
+            checkpoint.state = agent.get_attributes_from_agent().state;
+            checkpoint.progress = agent.save_progress()?;
+        }
+
+        match &self.run_dir {
+            Some(run_dir) => RunCheckpoint {
+                agents: self.pipeline.clone(),
+                usage: usage_ledger().records(),
+            }
+            .save(run_dir, &self.factsheet),
+            None => Ok(()),
         }
     }
+
+    // Run the pipeline, then report what its LLM calls cost
+    pub async fn execute_project(&mut self) -> Result<(), AutoGptError> {
+        let res = self.run_pipeline().await;
+
+        PrintCommand::AICall.print_agent_message(MANAGER_POSITION, "LLM usage of this run:");
+        usage_ledger().report().print();
+        res
+    }
+
+    async fn run_pipeline(&mut self) -> Result<(), AutoGptError> {
+        self.create_agent()?;
+        self.checkpoint()?;
+
+        let mut index = 0;
+        while index < self.agents.len() {
+            // Finished agents of a resumed run are skipped
+            while self.agents[index].get_attributes_from_agent().state != AgentState::Finished {
+                let agent_res = self.agents[index].execute_step(&mut self.factsheet).await;
+                self.checkpoint()?;
+
+                // Report the failure and stop - later agents depend on this one
+                if let Err(e) = agent_res {
+                    PrintCommand::Issue.print_agent_message(
+                        self.agents[index]
+                            .get_attributes_from_agent()
+                            .position
+                            .as_str(),
+                        format!("Failed: {}", e).as_str(),
+                    );
+                    return Err(e);
+                }
+
+                // Failing integration tests send the pipeline back to the backend developer
+                if self.factsheet.test_failures.is_some() {
+                    if let Some(backend) = self.backend_index(index) {
+                        self.agents[backend]
+                            .get_attributes_from_agent_mut()
+                            .update_state(AgentState::Working);
+                        self.checkpoint()?;
+                        index = backend;
+                    }
+                }
+            }
+            PrintCommand::AICall.print_agent_message(
+                MANAGER_POSITION,
+                format!(
+                    "{} finished",
+                    self.agents[index].get_attributes_from_agent().position
+                )
+                .as_str(),
+            );
+            index += 1;
+        }
+        Ok(())
+    }
+
+    // Backend developer running before the agent at `index`
+    fn backend_index(&self, index: usize) -> Option<usize> {
+        self.pipeline[..index]
+            .iter()
+            .position(|agent| agent.name.trim().eq_ignore_ascii_case(BACKEND_AGENT))
+    }
+
+    // Only build and test the code already in the project
+    pub async fn test_project(&mut self) -> Result<(), AutoGptError> {
+        let mut agent = AgentBackendDeveloper::new();
+        agent
+            .get_attributes_from_agent_mut()
+            .update_state(AgentState::UnitTesting);
+        agent.execute(&mut self.factsheet).await
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::apis::llm_provider::with_llm_provider;
+    use crate::apis::mock_provider::MockProvider;
+    use crate::helpers::config::{scratch_workspace, with_workspace_config, SCRATCH_SERVER};
+    use std::path::PathBuf;
+    use std::sync::Arc;
 
     #[tokio::test]
     async fn test_managing_agent() {
         let usr_request = "need a full stack app that fetches and tracks my fitness progress. Needs to include time zone infor from the web.";
-        let mut managing_agent = ManagingAgent::new(usr_request.to_string())
-            .await
-            .expect("Error creating managing agent");
 
-        managing_agent.execute_project().await;
+        // Fixtures for the architect, dependency free code so the scratch project builds offline
+        let code = SCRATCH_SERVER;
+        let provider = Arc::new(
+            MockProvider::from_fixtures(PathBuf::from(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/fixtures/llm"
+            )))
+            .with_response("print_backend_webserver_code", code)
+            .with_response("print_improved_webserver_code", code)
+            .with_response("print_rest_api_endpoints", "[]")
+            .with_response(
+                "print_integration_tests",
+                "include!(\"../src/main.rs\");\n\n#[test]\nfn test_server_builds() {}\n",
+            )
+            .with_response("print_frontend_code", "<h1>Fitness</h1>"),
+        );
+        let (_project, config) = scratch_workspace();
+
+        let run = with_llm_provider(provider.clone(), async {
+            let mut managing_agent = ManagingAgent::new(usr_request.to_string())
+                .await
+                .expect("Error creating managing agent");
+
+            managing_agent
+                .execute_project()
+                .await
+                .expect("Error executing project");
+            managing_agent
+        });
+        let managing_agent = with_workspace_config(config, run).await;
+
+        assert!(managing_agent.factsheet.project_scope.is_some());
+        assert_eq!(managing_agent.factsheet.backend_code.as_deref(), Some(code));
+        assert_eq!(
+            provider.calls(),
+            vec![
+                "convert_user_input_to_goal",
+                "print_project_scope",
+                "print_site_urls",
+                "print_database_schema",
+                "print_backend_webserver_code",
+                "print_improved_webserver_code",
+                "print_rest_api_endpoints",
+                "print_integration_tests",
+                "print_frontend_code",
+            ]
+        );
+        let factsheet = managing_agent.factsheet;
+        assert!(factsheet.database_schema.is_some());
+        assert_eq!(factsheet.api_endpoint_schema, Some(vec![]));
+        assert!(factsheet.integration_tests.is_some());
+        assert_eq!(factsheet.test_failures, None);
+        assert_eq!(
+            factsheet
+                .code_snapshots
+                .iter()
+                .map(|snapshot| snapshot.source.as_str())
+                .collect::<Vec<&str>>(),
+            vec![
+                "print_backend_webserver_code",
+                "print_improved_webserver_code"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resume_skips_finished_steps() {
+        let run_dir = tempfile::tempdir().expect("Failed to create run dir");
+        let mut factsheet = FactSheet::new("build a website that tells the time".to_string());
+        factsheet.backend_code = Some(SCRATCH_SERVER.to_string());
+
+        // Crashed while the backend developer was testing its code
+        RunCheckpoint {
+            agents: vec![
+                AgentCheckpoint {
+                    name: "architect".to_string(),
+                    state: AgentState::Finished,
+                    progress: None,
+                },
+                AgentCheckpoint {
+                    name: "backend".to_string(),
+                    state: AgentState::UnitTesting,
+                    progress: None,
+                },
+            ],
+            usage: vec![],
+        }
+        .save(run_dir.path(), &factsheet)
+        .expect("Failed to save checkpoint");
+
+        let provider =
+            Arc::new(MockProvider::new().with_response("print_rest_api_endpoints", "[]"));
+        let (_project, config) = scratch_workspace();
+
+        let mut managing_agent =
+            ManagingAgent::resume(run_dir.path()).expect("Error resuming managing agent");
+        with_workspace_config(
+            config,
+            with_llm_provider(provider.clone(), managing_agent.execute_project()),
+        )
+        .await
+        .expect("Error executing project");
+
+        assert_eq!(provider.calls(), vec!["print_rest_api_endpoints"]);
+        let (_, checkpoint) = RunCheckpoint::load(run_dir.path()).expect("Missing checkpoint");
+        assert!(checkpoint
+            .expect("Missing checkpoint")
+            .agents
+            .iter()
+            .all(|agent| agent.state == AgentState::Finished));
+    }
+
+    #[tokio::test]
+    async fn test_resume_keeps_pending_bugs() {
+        let run_dir = tempfile::tempdir().expect("Failed to create run dir");
+        let mut factsheet = FactSheet::new("build a website that tells the time".to_string());
+        factsheet.backend_code = Some("fn main() { broken".to_string());
+
+        // Crashed after the build failed, before the fix was asked for
+        RunCheckpoint {
+            agents: vec![AgentCheckpoint {
+                name: "backend".to_string(),
+                state: AgentState::Working,
+                progress: Some(serde_json::json!({
+                    "bug_errors": "error: expected `}`",
+                    "bug_count": 1
+                })),
+            }],
+            usage: vec![],
+        }
+        .save(run_dir.path(), &factsheet)
+        .expect("Failed to save checkpoint");
+
+        let provider = Arc::new(
+            MockProvider::new()
+                .with_response("print_fixed_code", SCRATCH_SERVER)
+                .with_response("print_rest_api_endpoints", "[]"),
+        );
+        let (_project, config) = scratch_workspace();
+
+        let mut managing_agent =
+            ManagingAgent::resume(run_dir.path()).expect("Error resuming managing agent");
+        with_workspace_config(
+            config,
+            with_llm_provider(provider.clone(), managing_agent.execute_project()),
+        )
+        .await
+        .expect("Error executing project");
 
-        dbg!(managing_agent.factsheet);
+        // The pending bug is fixed, not mistaken for a first improvement
+        assert_eq!(
+            provider.calls(),
+            vec!["print_fixed_code", "print_rest_api_endpoints"]
+        );
+        let fix_prompt = &provider.messages()[0][0].content;
+        assert!(fix_prompt.contains("expected `}`"), "{}", fix_prompt);
     }
 }
@@ -1 +1,3 @@
+pub mod agent_registry;
 pub mod managing_agent;
+pub mod run_checkpoint;
@@ -0,0 +1,128 @@
+use crate::apis::usage::{UsageRecord, UsageReport};
+use crate::helpers::general::write_file;
+use crate::models::agent_basic::basic_agent::AgentState;
+use crate::models::agents::agent_traits::FactSheet;
+use crate::models::general::error::AutoGptError;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const FACTSHEET_FILE: &str = "factsheet.json";
+pub const CHECKPOINT_FILE: &str = "checkpoint.json";
+pub const COST_REPORT_FILE: &str = "cost_report.json";
+
+// State of one agent of the pipeline
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentCheckpoint {
+    pub name: String,
+    pub state: AgentState,
+    // Pending errors and retry counts, see `SpecialFunctions::save_progress`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub progress: Option<serde_json::Value>,
+}
+
+// Progress of a run, saved next to its factsheet after every state transition
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunCheckpoint {
+    pub agents: Vec<AgentCheckpoint>,
+    // LLM calls made so far, so a resumed run keeps counting towards the same budget
+    #[serde(default)]
+    pub usage: Vec<UsageRecord>,
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), AutoGptError> {
+    let contents = serde_json::to_string_pretty(value).map_err(|source| AutoGptError::Decode {
+        function: path.display().to_string(),
+        source,
+    })?;
+
+    // Write then rename so a crash never leaves a half written checkpoint
+    let tmp_path = path.with_extension("json.tmp");
+    write_file(&tmp_path, &contents)?;
+    fs::rename(&tmp_path, path).map_err(AutoGptError::io(path.display().to_string()))
+}
+
+fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T, AutoGptError> {
+    let contents =
+        fs::read_to_string(path).map_err(AutoGptError::io(path.display().to_string()))?;
+    serde_json::from_str(&contents).map_err(|source| AutoGptError::Decode {
+        function: path.display().to_string(),
+        source,
+    })
+}
+
+// Factsheet file, or the factsheet of a run directory
+pub fn factsheet_path(path: &Path) -> PathBuf {
+    match path.is_dir() {
+        true => path.join(FACTSHEET_FILE),
+        false => path.to_path_buf(),
+    }
+}
+
+pub fn read_factsheet(path: &Path) -> Result<FactSheet, AutoGptError> {
+    read_json(&factsheet_path(path))
+}
+
+pub fn write_factsheet(path: &Path, factsheet: &FactSheet) -> Result<(), AutoGptError> {
+    write_json(&factsheet_path(path), factsheet)
+}
+
+impl RunCheckpoint {
+    pub fn save(&self, run_dir: &Path, factsheet: &FactSheet) -> Result<(), AutoGptError> {
+        write_json(&run_dir.join(FACTSHEET_FILE), factsheet)?;
+        write_json(
+            &run_dir.join(COST_REPORT_FILE),
+            &UsageReport::from_records(&self.usage),
+        )?;
+        write_json(&run_dir.join(CHECKPOINT_FILE), self)
+    }
+
+    // Factsheet of a run, with its checkpoint if the pipeline got that far
+    pub fn load(run_dir: &Path) -> Result<(FactSheet, Option<Self>), AutoGptError> {
+        let factsheet = read_json(&run_dir.join(FACTSHEET_FILE))?;
+        let checkpoint_path = run_dir.join(CHECKPOINT_FILE);
+        let checkpoint = match checkpoint_path.exists() {
+            true => Some(read_json(&checkpoint_path)?),
+            false => None,
+        };
+        Ok((factsheet, checkpoint))
+    }
+}
+
+// Fresh directory for a run inside `runs_dir`
+pub fn new_run_dir(runs_dir: &Path) -> PathBuf {
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    runs_dir.join(format!("run-{}", started))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkpoint_round_trip() {
+        let run_dir = tempfile::tempdir().expect("Failed to create run dir");
+        let factsheet = FactSheet::new("build a website that tells the time".to_string());
+        let checkpoint = RunCheckpoint {
+            agents: vec![AgentCheckpoint {
+                name: "architect".to_string(),
+                state: AgentState::UnitTesting,
+                progress: None,
+            }],
+            usage: vec![],
+        };
+
+        checkpoint
+            .save(run_dir.path(), &factsheet)
+            .expect("Failed to save checkpoint");
+        let (loaded_factsheet, loaded_checkpoint) =
+            RunCheckpoint::load(run_dir.path()).expect("Failed to load checkpoint");
+
+        assert_eq!(loaded_factsheet, factsheet);
+        assert_eq!(loaded_checkpoint, Some(checkpoint));
+    }
+}
@@ -3,21 +3,33 @@ use crate::ai_functions::aifunc_backend::{
     print_rest_api_endpoints,
 };
 use crate::helpers::general::{
-    check_status_code, read_code_template_contents, read_exec_main_contents, save_api_endpoints,
-    save_backend_code, WEB_SERVER_PROJECT_PATH,
+    install_auth_module, read_code_template_contents, read_exec_main_contents, save_api_endpoints,
+    save_backend_code, save_openapi_document,
 };
 
-use crate::helpers::command_line::{confirm_safe_code, PrintCommand};
-use crate::helpers::general::ai_task_request;
+use crate::helpers::command_line::{confirm_safe_code, print_code_diff, CodeReview, PrintCommand};
+use crate::helpers::config::{workspace_config, WorkspaceConfig};
+use crate::helpers::contract_tests::{
+    is_session_route, run_auth_checks, run_contract_tests, run_logout_check, ContractReport,
+};
+use crate::helpers::database_schema::{migration_sql, INITIAL_MIGRATION};
+use crate::helpers::diagnostics::{apply_suggestions, compiler_errors, format_errors, MAIN_FILE};
+use crate::helpers::general::{ai_task_request_decoded, ai_task_request_rust_code};
+use crate::helpers::openapi::openapi_document;
+use crate::helpers::route_extraction::extract_routes;
+use crate::helpers::safety_scan::{hosts_of, scan_code};
+use crate::helpers::sandbox::{free_port, Sandbox};
 use crate::models::agent_basic::basic_agent::{AgentState, BasicAgent};
-use crate::models::agents::agent_traits::{FactSheet, RouteObject, SpecialFunctions};
+use crate::models::agents::agent_traits::{
+    progress_from_value, progress_to_value, FactSheet, RouteObject, SpecialFunctions,
+};
+use crate::models::general::error::AutoGptError;
 
 use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
 use reqwest::Client;
-use std::env::current_dir;
-use std::process::{Command, Stdio};
+use serde::{Deserialize, Serialize};
 use std::time::Duration;
-use tokio::time;
 
 #[derive(Debug)]
 pub struct AgentBackendDeveloper {
@@ -26,6 +38,13 @@ pub struct AgentBackendDeveloper {
     bug_count: u8,
 }
 
+// Pending bugs survive a resume, so a fix is not mistaken for a first improvement
+#[derive(Debug, Serialize, Deserialize)]
+struct BackendProgress {
+    bug_errors: Option<String>,
+    bug_count: u8,
+}
+
 impl AgentBackendDeveloper {
     pub fn new() -> Self {
         let attributes = BasicAgent {
@@ -42,83 +61,257 @@ impl AgentBackendDeveloper {
         }
     }
 
-    async fn call_initial_backend_code(&mut self, factsheet: &mut FactSheet) {
-        let code_template_str = read_code_template_contents();
+    async fn call_initial_backend_code(
+        &mut self,
+        factsheet: &mut FactSheet,
+    ) -> Result<(), AutoGptError> {
+        let code_template_str = read_code_template_contents()?;
 
         // Concatenate instruction
-        let mut msg_context = format!(
-            "CODE TEMPLATE: {} \n PROJECT_DESCRIPTION: {} \n",
-            code_template_str, factsheet.project_description
+        let msg_context = format!(
+            "CODE TEMPLATE: {} \n PROJECT_DESCRIPTION: {} \n{}{}",
+            code_template_str,
+            factsheet.project_description,
+            Self::database_context(factsheet)?,
+            Self::auth_context(factsheet)?
         );
 
-        let ai_response = ai_task_request(
+        let ai_response = ai_task_request_rust_code(
             msg_context,
-            &self.attributes.position,
+            &mut self.attributes,
             get_function_string!(print_backend_webserver_code),
             print_backend_webserver_code,
         )
-        .await;
+        .await?;
+
+        save_backend_code(&ai_response)?;
+        factsheet.set_backend_code(
+            get_function_string!(print_backend_webserver_code),
+            ai_response,
+        );
+        Ok(())
+    }
+
+    // Entities of the database designer and the migration that creates them, if there are any
+    fn database_context(factsheet: &FactSheet) -> Result<String, AutoGptError> {
+        let Some(schema) = &factsheet.database_schema else {
+            return Ok(String::new());
+        };
+        let schema_str =
+            serde_json::to_string_pretty(schema).map_err(|source| AutoGptError::Decode {
+                function: "database_schema".to_string(),
+                source,
+            })?;
+        Ok(format!(
+            " DATABASE_STORE: {} \n DATABASE_SCHEMA: {} \n MIGRATION ({}): {} \n",
+            workspace_config()?.database.store.name(),
+            schema_str,
+            INITIAL_MIGRATION,
+            migration_sql(schema)
+        ))
+    }
 
-        save_backend_code(&ai_response);
-        factsheet.backend_code = Some(ai_response);
+    // Sites with login get the vetted auth module, the LLM only wires it up
+    fn auth_context(factsheet: &FactSheet) -> Result<String, AutoGptError> {
+        if !factsheet.is_login_required() {
+            return Ok(String::new());
+        }
+        let module = install_auth_module()?;
+        Ok(format!(
+            " AUTH_MODULE (src/auth.rs, already in the project, do not change it): {} \n
+            USE THE AUTH MODULE: declare `mod auth;`, store users with auth::hash_password and check them with auth::verify_password.
+            POST /register and POST /login take {{\"username\", \"password\"}}, /login returns {{\"token\": ...}} from Sessions::create and POST /logout revokes it.
+            Register `.app_data(web::Data::new(auth::Sessions::new()))` once, outside the HttpServer::new closure,
+            and put every other route in `web::scope(\"\").wrap(actix_web::middleware::from_fn(auth::require_session))`.
+            Handlers read the user with an `auth::AuthenticatedUser` argument. \n",
+            module
+        ))
     }
 
-    async fn call_improved_backend_code(&mut self, factsheet: &mut FactSheet) {
-        // Display generated code
-        let mut msg_context = format!(
-            "CODE TEMPLATE: {:?} \n PROJECT_DESCRIPTION: {:?} \n",
-            factsheet.backend_code, factsheet
+    // Protected routes must answer 401 to anonymous requests, then the rest is tested logged in
+    async fn run_endpoint_tests(
+        factsheet: &FactSheet,
+        base_url: &str,
+        endpoints: &[RouteObject],
+    ) -> Result<ContractReport, AutoGptError> {
+        let anonymous = Client::builder()
+            .timeout(Duration::from_secs(5))
+            .build()
+            .map_err(|e| AutoGptError::EndpointTest(e.to_string()))?;
+        if !factsheet.is_login_required() {
+            return Ok(run_contract_tests(&anonymous, base_url, endpoints).await);
+        }
+
+        let (mut report, token) = run_auth_checks(&anonymous, base_url, endpoints).await;
+        // Without a session the other routes cannot be tested, the auth failures say why
+        let Some(token) = token else {
+            return Ok(report);
+        };
+
+        let mut headers = HeaderMap::new();
+        let bearer = HeaderValue::from_str(&format!("Bearer {}", token))
+            .map_err(|e| AutoGptError::EndpointTest(e.to_string()))?;
+        headers.insert(AUTHORIZATION, bearer);
+        let client = Client::builder()
+            .timeout(Duration::from_secs(5))
+            .default_headers(headers)
+            .build()
+            .map_err(|e| AutoGptError::EndpointTest(e.to_string()))?;
+        let protected: Vec<RouteObject> = endpoints
+            .iter()
+            .filter(|endpoint| !is_session_route(&endpoint.route))
+            .cloned()
+            .collect();
+        let logged_in = run_contract_tests(&client, base_url, &protected).await;
+        report.results.extend(logged_in.results);
+        // Logout revokes the session, so it comes last
+        let logged_out = run_logout_check(&client, base_url, endpoints).await;
+        report.results.extend(logged_out.results);
+        Ok(report)
+    }
+
+    async fn call_improved_backend_code(
+        &mut self,
+        factsheet: &mut FactSheet,
+    ) -> Result<(), AutoGptError> {
+        // Only what the code should do, the code itself is already the template
+        let scope_str = serde_json::to_string(&factsheet.project_scope).map_err(|source| {
+            AutoGptError::Decode {
+                function: "project_scope".to_string(),
+                source,
+            }
+        })?;
+        let msg_context = format!(
+            "CODE TEMPLATE: {} \n PROJECT_DESCRIPTION: {} \n PROJECT_SCOPE: {} \n{}",
+            factsheet.backend_code.as_deref().unwrap_or_default(),
+            factsheet.project_description,
+            scope_str,
+            Self::database_context(factsheet)?
         );
 
         // Generate improved code
-        let ai_response = ai_task_request(
+        let ai_response = ai_task_request_rust_code(
             msg_context,
-            &self.attributes.position,
+            &mut self.attributes,
             get_function_string!(print_improved_webserver_code),
             print_improved_webserver_code,
         )
-        .await;
+        .await?;
 
-        save_backend_code(&ai_response);
-        factsheet.backend_code = Some(ai_response);
+        save_backend_code(&ai_response)?;
+        factsheet.set_backend_code(
+            get_function_string!(print_improved_webserver_code),
+            ai_response,
+        );
+        Ok(())
     }
 
-    async fn call_fix_code_bugs(&mut self, factsheet: &mut FactSheet) {
+    async fn call_fix_code_bugs(&mut self, factsheet: &mut FactSheet) -> Result<(), AutoGptError> {
         // Display error and bugs
-        let mut msg_context = format!(
-            "BROKE_CODE: {:?} \n ERROR_BUGS: {:?} \n
+        let msg_context = format!(
+            "BROKE_CODE: {} \n ERROR_BUGS: {} \n
             THIS FUNCTIONS ONLY OUTPUTS CODE. JUST OUTPUT THE CODE.",
-            factsheet.backend_code, self.bug_errors
+            factsheet.backend_code.as_deref().unwrap_or_default(),
+            self.bug_errors.as_deref().unwrap_or_default()
         );
 
         // Generate fixed code
-        let ai_response = ai_task_request(
+        let ai_response = ai_task_request_rust_code(
             msg_context,
-            &self.attributes.position,
+            &mut self.attributes,
             get_function_string!(print_fixed_code),
             print_fixed_code,
         )
-        .await;
+        .await?;
 
-        save_backend_code(&ai_response);
-        factsheet.backend_code = Some(ai_response);
+        save_backend_code(&ai_response)?;
+        factsheet.set_backend_code(get_function_string!(print_fixed_code), ai_response);
+        Ok(())
     }
 
-    async fn call_extract_rest_api_endpoints(&self) -> String {
-        let backend_code = read_exec_main_contents();
+    async fn call_extract_rest_api_endpoints(&mut self) -> Result<Vec<RouteObject>, AutoGptError> {
+        let backend_code = read_exec_main_contents()?;
+
+        // Routes are read from the code itself, the LLM is only asked when none are found
+        if let Ok(routes) = extract_routes(&backend_code) {
+            if !routes.is_empty() {
+                PrintCommand::UnitTest.print_agent_message(
+                    self.attributes.position.as_str(),
+                    format!("Extracted {} routes from the code", routes.len()).as_str(),
+                );
+                return Ok(routes);
+            }
+        }
 
         let msg_context = format!("CODE_INPUT: {}", backend_code);
 
-        // Generate fixed code
-        let ai_response = ai_task_request(
+        // Extract endpoint schema
+        ai_task_request_decoded::<Vec<RouteObject>>(
             msg_context,
-            &self.attributes.position,
+            &mut self.attributes,
             get_function_string!(print_rest_api_endpoints),
             print_rest_api_endpoints,
         )
-        .await;
+        .await
+    }
+
+    // Send a problem of the running server back for fixing, gives up after too many
+    fn endpoint_bug(&mut self, bug: String) -> Result<(), AutoGptError> {
+        self.bug_count += 1;
+        if self.bug_count > 10 {
+            return Err(AutoGptError::EndpointTest(bug));
+        }
+        self.bug_errors = Some(bug);
+        self.attributes.state = AgentState::Working;
+        Ok(())
+    }
+
+    // Scan the generated code before anything runs it, rejecting it if the policy says so
+    fn review_generated_code(
+        &self,
+        config: &WorkspaceConfig,
+        factsheet: &FactSheet,
+    ) -> Result<(), AutoGptError> {
+        let code = read_exec_main_contents()?;
+
+        let mut allowed_hosts = config.safety.allowed_hosts.clone();
+        if let Some(external_urls) = &factsheet.external_urls {
+            allowed_hosts.extend(hosts_of(external_urls));
+        }
+        let report = scan_code(&code, &allowed_hosts).map_err(|e| {
+            AutoGptError::BuildFailure(format!("Generated code does not parse as Rust: {}", e))
+        })?;
+
+        // Show the reviewer what changed since the previous iteration, or since the template
+        if !config.non_interactive {
+            match factsheet.previous_snapshot() {
+                Some(previous) => print_code_diff(
+                    format!("iteration {}", previous.iteration).as_str(),
+                    &previous.code,
+                    format!("iteration {}", factsheet.code_snapshots.len()).as_str(),
+                    &code,
+                ),
+                None => print_code_diff(
+                    "code_template.rs",
+                    &read_code_template_contents()?,
+                    "main.rs",
+                    &code,
+                ),
+            }
+        }
+        let print_command = match report.findings.is_empty() {
+            true => PrintCommand::UnitTest,
+            false => PrintCommand::Issue,
+        };
+        print_command.print_agent_message(
+            self.attributes.position.as_str(),
+            format!("Safety scan of generated code:\n{}", report).as_str(),
+        );
 
-        ai_response
+        if config.safety.auto_reject && report.has_high_severity() {
+            return Err(AutoGptError::UnsafeCode(report.to_string()));
+        }
+        Ok(())
     }
 }
 
@@ -127,174 +320,219 @@ impl SpecialFunctions for AgentBackendDeveloper {
     fn get_attributes_from_agent(&self) -> &BasicAgent {
         &self.attributes
     }
-    async fn execute(
-        &mut self,
-        factsheet: &mut FactSheet,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        while self.attributes.state != AgentState::Finished {
-            match &self.attributes.state {
-                AgentState::Discovery => {
-                    self.call_initial_backend_code(factsheet).await;
-                    self.attributes.state = AgentState::Working;
-                    continue;
-                }
-                AgentState::Working => {
-                    if self.bug_count == 0 {
-                        self.call_improved_backend_code(factsheet).await;
-                    } else {
-                        self.call_fix_code_bugs(factsheet).await;
-                    }
-                    self.attributes.state = AgentState::UnitTesting;
-                    continue;
+
+    fn get_attributes_from_agent_mut(&mut self) -> &mut BasicAgent {
+        &mut self.attributes
+    }
+
+    fn save_progress(&self) -> Result<Option<serde_json::Value>, AutoGptError> {
+        progress_to_value(
+            &self.attributes.position,
+            &BackendProgress {
+                bug_errors: self.bug_errors.clone(),
+                bug_count: self.bug_count,
+            },
+        )
+    }
+
+    fn restore_progress(&mut self, progress: serde_json::Value) -> Result<(), AutoGptError> {
+        let progress: BackendProgress = progress_from_value(&self.attributes.position, progress)?;
+        self.bug_errors = progress.bug_errors;
+        self.bug_count = progress.bug_count;
+        Ok(())
+    }
+    async fn execute_step(&mut self, factsheet: &mut FactSheet) -> Result<(), AutoGptError> {
+        match &self.attributes.state {
+            AgentState::Discovery => {
+                self.call_initial_backend_code(factsheet).await?;
+                self.attributes.state = AgentState::Working;
+            }
+            AgentState::Working => {
+                // Integration tests the QA tester found failing are fixed like any other bug
+                if let Some(failures) = factsheet.test_failures.take() {
+                    self.bug_errors = Some(format!("INTEGRATION TESTS FAILED:\n{}", failures));
+                    self.call_fix_code_bugs(factsheet).await?;
+                } else if self.bug_count == 0 {
+                    self.call_improved_backend_code(factsheet).await?;
+                } else {
+                    self.call_fix_code_bugs(factsheet).await?;
                 }
-                AgentState::UnitTesting => {
-                    // Guard: ensure AI safety
+                self.attributes.state = AgentState::UnitTesting;
+            }
+            AgentState::UnitTesting => {
+                let config = workspace_config()?;
+
+                // Guard: ensure AI safety
+                self.review_generated_code(&config, factsheet)?;
+                if config.non_interactive {
                     PrintCommand::UnitTest.print_agent_message(
                         self.attributes.position.as_str(),
-                        "Backend code unit testing: requring user input",
+                        "Backend code unit testing: non-interactive, skipping user review",
                     );
-
-                    // Get user input
-                    let is_safe_code = confirm_safe_code();
-                    if !is_safe_code {
-                        panic!("Code is not safe to run");
-                    }
-
-                    // BUild and test code
+                } else {
                     PrintCommand::UnitTest.print_agent_message(
                         self.attributes.position.as_str(),
-                        "Backend code unit testing: building",
+                        "Backend code unit testing: requring user input",
                     );
 
-                    let build_backend_server = Command::new("cargo")
-                        .arg("build")
-                        .current_dir(WEB_SERVER_PROJECT_PATH)
-                        .stdout(Stdio::piped())
-                        .stderr(Stdio::piped())
-                        .output()
-                        .expect("Failed to build backend server");
-                    // Check if build was successful
-                    if build_backend_server.status.success() {
-                        self.bug_count = 0;
-                        PrintCommand::UnitTest.print_agent_message(
-                            self.attributes.position.as_str(),
-                            "Backend code unit testing: build successful",
-                        );
-                    } else {
-                        let err_arr = build_backend_server.stderr;
-                        let err_str = String::from_utf8(err_arr).unwrap();
-                        // update error status
-                        self.bug_count += 1;
-                        self.bug_errors = Some(err_str);
-
-                        // Exit if too many errors
-                        if self.bug_count > 10 {
-                            PrintCommand::UnitTest.print_agent_message(
-                                self.attributes.position.as_str(),
-                                "Backend code unit testing: too many errors",
-                            );
-                            panic!("Error: Too many errors in code");
-                        }
-
-                        // back to working state
-                        self.attributes.state = AgentState::Working;
-                        continue;
-                    };
-
-                    // Extract and test API endpoints
-                    let api_endpoints_str = self.call_extract_rest_api_endpoints().await;
-
-                    // Convert api enpoints to values
-                    let api_enpoints: Vec<RouteObject> =
-                        serde_json::from_str(api_endpoints_str.as_str())
-                            .expect("Error parsing api endpoints");
-
-                    // Check endpoints
-                    let check_endpoints: Vec<RouteObject> = api_enpoints
+                    // Get user input - earlier iterations can be restored and reviewed again
+                    let earlier_iterations: Vec<usize> = factsheet
+                        .code_snapshots
                         .iter()
-                        .filter(|&ro| ro.method == "GET" && ro.is_route_dynamic == false)
-                        .cloned()
+                        .map(|snapshot| snapshot.iteration)
+                        .filter(|&iteration| iteration < factsheet.code_snapshots.len())
                         .collect();
+                    match confirm_safe_code(&earlier_iterations) {
+                        CodeReview::Approve => {}
+                        CodeReview::Reject => {
+                            return Err(AutoGptError::UserAbort(
+                                "Code is not safe to run".to_string(),
+                            ))
+                        }
+                        CodeReview::RollBack(iteration) => {
+                            if let Some(code) = factsheet.roll_back_to(iteration) {
+                                save_backend_code(&code)?;
+                            }
+                            return Ok(());
+                        }
+                    }
+                }
 
-                    // Save api endpoints
-                    factsheet.api_endpoint_schema = Some(check_endpoints.clone());
-
-                    // Run backend server
+                // BUild and test code in a sandboxed copy of the project
+                PrintCommand::UnitTest.print_agent_message(
+                    self.attributes.position.as_str(),
+                    "Backend code unit testing: building",
+                );
+
+                let sandbox = Sandbox::new(&config.project_dir, &config.sandbox)?;
+                let build_backend_server = sandbox.build().await?;
+                // Check if build was successful
+                // The bug count is kept, failing endpoint tests also count towards the limit
+                if build_backend_server.success {
                     PrintCommand::UnitTest.print_agent_message(
                         self.attributes.position.as_str(),
-                        "Backend code unit testing: running server",
+                        "Backend code unit testing: build successful",
                     );
-                    let mut run_backend_server = Command::new("cargo")
-                        .arg("run")
-                        .current_dir(WEB_SERVER_PROJECT_PATH)
-                        .stdout(Stdio::piped())
-                        .stderr(Stdio::piped())
-                        .spawn()
-                        .expect("Failed to run backend server");
+                } else {
+                    // Only the compile errors go to the LLM, the raw output when there are none
+                    let errors = compiler_errors(&build_backend_server.diagnostics);
+                    let err_str = match errors.is_empty() {
+                        true => build_backend_server.stderr,
+                        false => format_errors(&errors),
+                    };
+                    // update error status
+                    self.bug_count += 1;
 
-                    PrintCommand::UnitTest.print_agent_message(
-                        self.attributes.position.as_str(),
-                        "Backend code unit testing: launching tests on server in 5 secs",
-                    );
-                    let sec_sleep = time::Duration::from_secs(5);
-                    time::sleep(sec_sleep).await;
-                    // check status code
-                    for endpoint in check_endpoints {
-                        let testing_msg = format!("Testing endpoint: {}", endpoint.route);
+                    // Exit if too many errors
+                    if self.bug_count > 10 {
                         PrintCommand::UnitTest.print_agent_message(
                             self.attributes.position.as_str(),
-                            testing_msg.as_str(),
+                            "Backend code unit testing: too many errors",
                         );
+                        return Err(AutoGptError::BuildFailure(err_str));
+                    }
 
-                        // create client
-                        let client = Client::builder()
-                            .timeout(Duration::from_secs(5))
-                            .build()
-                            .unwrap();
-
-                        let url = format!("http://localhost:8000{}", endpoint.route);
-                        match check_status_code(&client, &url).await {
-                            Ok(status_code) => {
-                                if status_code != 200 {
-                                    let err_msg = format!(
-                                        "Error: Status code is not 200 for endpoint: {}",
-                                        endpoint.route
-                                    );
-                                    PrintCommand::Issue.print_agent_message(
-                                        self.attributes.position.as_str(),
-                                        testing_msg.as_str(),
-                                    );
-                                }
-                            }
-
-                            Err(e) => {
-                                // kill server - kill $(lsof -t -i:8000)
-                                run_backend_server
-                                    .kill()
-                                    .expect("Failed to kill backend server");
-                                // error msg
-                                let err_msg = format!("Error checking backend {}", e);
-                                PrintCommand::Issue.print_agent_message(
-                                    self.attributes.position.as_str(),
-                                    err_msg.as_str(),
-                                );
-                            }
-                        }
+                    // Fixes rustc is sure about are applied without the LLM, then the code is tested again
+                    let built_code = read_exec_main_contents()?;
+                    if let Some(fixed_code) =
+                        apply_suggestions(&built_code, MAIN_FILE, &build_backend_server.diagnostics)
+                    {
+                        PrintCommand::UnitTest.print_agent_message(
+                            self.attributes.position.as_str(),
+                            "Backend code unit testing: applied compiler suggestions",
+                        );
+                        save_backend_code(&fixed_code)?;
+                        factsheet.set_backend_code("rustc suggestions", fixed_code);
+                        return Ok(());
                     }
-                    save_api_endpoints(&api_endpoints_str);
+                    self.bug_errors = Some(err_str);
 
+                    // back to working state
+                    self.attributes.state = AgentState::Working;
+                    return Ok(());
+                };
+
+                // Extract and test API endpoints
+                let api_enpoints = self.call_extract_rest_api_endpoints().await?;
+
+                // Save api endpoints
+                factsheet.api_endpoint_schema = Some(api_enpoints.clone());
+                let api_endpoints_str =
+                    serde_json::to_string_pretty(&api_enpoints).map_err(|source| {
+                        AutoGptError::Decode {
+                            function: get_function_string!(print_rest_api_endpoints).to_string(),
+                            source,
+                        }
+                    })?;
+                save_api_endpoints(&api_endpoints_str)?;
+                let openapi = openapi_document(&factsheet.project_description, &api_enpoints);
+                save_openapi_document(&format!("{:#}", openapi))?;
+
+                // Run backend server
+                PrintCommand::UnitTest.print_agent_message(
+                    self.attributes.position.as_str(),
+                    "Backend code unit testing: running server",
+                );
+                let port = match config.server_port {
+                    0 => free_port()?,
+                    port => port,
+                };
+                let mut run_backend_server = sandbox.spawn_server(port).await?;
+
+                PrintCommand::UnitTest.print_agent_message(
+                    self.attributes.position.as_str(),
+                    format!(
+                        "Backend code unit testing: waiting for server on port {}",
+                        port
+                    )
+                    .as_str(),
+                );
+                let startup_timeout = Duration::from_secs(config.sandbox.startup_timeout_secs);
+                if let Err(failure) = run_backend_server.wait_until_ready(startup_timeout).await {
+                    run_backend_server.stop().await;
                     PrintCommand::Issue.print_agent_message(
                         self.attributes.position.as_str(),
-                        "Backend code unit testing: all tests passed",
+                        failure.to_string().as_str(),
                     );
-                    run_backend_server
-                        .kill()
-                        .expect("Failed to kill backend server on completion");
+                    return self.endpoint_bug(format!("SERVER FAILED TO START:\n{}", failure));
+                }
 
-                    self.attributes.state = AgentState::Finished;
+                // Call every endpoint with example payloads and check the responses against the schema
+                let base_url = format!("http://127.0.0.1:{}", run_backend_server.port());
+                let report = Self::run_endpoint_tests(factsheet, &base_url, &api_enpoints).await;
+
+                // kill server
+                run_backend_server.stop().await;
+                let report = report?;
+
+                let failures = report.failures();
+                let print_command = match failures.is_empty() {
+                    true => PrintCommand::UnitTest,
+                    false => PrintCommand::Issue,
+                };
+                print_command.print_agent_message(
+                    self.attributes.position.as_str(),
+                    format!("Backend code unit testing: endpoint tests\n{}", report).as_str(),
+                );
+
+                // Failing endpoints go back to the LLM like build errors
+                if !failures.is_empty() {
+                    let err_str = failures
+                        .iter()
+                        .map(|failure| failure.to_string())
+                        .collect::<Vec<String>>()
+                        .join("\n");
+                    return self.endpoint_bug(format!("ENDPOINT TESTS FAILED:\n{}", err_str));
                 }
-                _ => {}
+
+                PrintCommand::UnitTest.print_agent_message(
+                    self.attributes.position.as_str(),
+                    "Backend code unit testing: all tests passed",
+                );
+
+                self.attributes.state = AgentState::Finished;
             }
+            _ => {}
         }
         Ok(())
     }
@@ -303,6 +541,12 @@ impl SpecialFunctions for AgentBackendDeveloper {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::apis::llm_provider::with_llm_provider;
+    use crate::apis::mock_provider::MockProvider;
+    use crate::helpers::config::{scratch_workspace, with_workspace_config, SCRATCH_SERVER};
+    use crate::models::agents::agent_traits::ProjectScope;
+    use std::path::PathBuf;
+    use std::sync::Arc;
 
     #[tokio::test]
     async fn test_writing_backend_code() {
@@ -327,9 +571,103 @@ mod tests {
         // agent.attributes.state = AgentState::UnitTesting;
         agent.attributes.state = AgentState::Discovery;
 
-        agent
-            .execute(&mut factsheet)
-            .await
-            .expect("Error executing backend developer agent");
+        // Dependency free server so the scratch project builds offline
+        let code = SCRATCH_SERVER;
+        let provider = Arc::new(
+            MockProvider::new()
+                .with_response("print_backend_webserver_code", code)
+                .with_response("print_improved_webserver_code", code)
+                .with_response("print_rest_api_endpoints", "[]"),
+        );
+        let (_project, config) = scratch_workspace();
+
+        with_workspace_config(
+            config.clone(),
+            with_llm_provider(provider.clone(), agent.execute(&mut factsheet)),
+        )
+        .await
+        .expect("Error executing backend developer agent");
+
+        assert_eq!(factsheet.backend_code.as_deref(), Some(code));
+        assert_eq!(factsheet.api_endpoint_schema, Some(vec![]));
+        assert!(config.api_schema_path.exists());
+
+        // The improve prompt carries the description, not a dump of the factsheet
+        let improve_prompt = &provider.messages()[1][0].content;
+        assert!(improve_prompt.contains("PROJECT_DESCRIPTION: build a website that fetches"));
+        assert!(!improve_prompt.contains("code_snapshots"));
+        assert!(config.openapi_path().exists());
+    }
+
+    #[tokio::test]
+    async fn test_fix_prompt_keeps_diagnostics_formatting() {
+        let mut agent = AgentBackendDeveloper::new();
+        agent.attributes.state = AgentState::Working;
+        agent.bug_count = 1;
+        agent.bug_errors =
+            Some("error[E0308]: mismatched types\n --> src/main.rs:2:18".to_string());
+        let mut factsheet = FactSheet::new("build a website that tells the time".to_string());
+        factsheet.backend_code = Some("fn main() {\n    let port: u16 = \"8080\";\n}".to_string());
+
+        let provider =
+            Arc::new(MockProvider::new().with_response("print_fixed_code", SCRATCH_SERVER));
+        let (_project, config) = scratch_workspace();
+        with_workspace_config(
+            config,
+            with_llm_provider(provider.clone(), agent.execute_step(&mut factsheet)),
+        )
+        .await
+        .expect("Error fixing backend code");
+
+        // Diagnostics and code reach the model as text, not as an escaped Debug string
+        let fix_prompt = &provider.messages()[0][0].content;
+        assert!(
+            fix_prompt.contains("mismatched types\n --> src/main.rs:2:18"),
+            "{}",
+            fix_prompt
+        );
+        assert!(fix_prompt.contains("fn main() {\n    let port"));
+        assert!(!fix_prompt.contains("Some(\""));
+    }
+
+    #[tokio::test]
+    async fn test_login_installs_auth_module() {
+        let mut factsheet = FactSheet::new("build a todo app with user login".to_string());
+        let (_project, mut config) = scratch_workspace();
+        // The vetted module itself, not the scratch workspace's stub
+        let vetted = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../web_template/src/auth.rs");
+        config.auth_module_path = vetted.clone();
+
+        let context = with_workspace_config(config.clone(), async {
+            assert_eq!(AgentBackendDeveloper::auth_context(&factsheet)?, "");
+            factsheet.project_scope = Some(ProjectScope {
+                is_crud_required: true,
+                is_user_login_and_logout: true,
+                is_external_urls_required: false,
+            });
+            AgentBackendDeveloper::auth_context(&factsheet)
+        })
+        .await
+        .expect("Error installing auth module");
+
+        let installed = std::fs::read_to_string(config.project_auth_module_path()).unwrap();
+        assert_eq!(installed, std::fs::read_to_string(vetted).unwrap());
+        assert!(context.contains(&installed));
+        let module = syn::parse_file(&installed).expect("Auth module is not valid Rust");
+        let public_fns: Vec<String> = module
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                syn::Item::Fn(f) if matches!(f.vis, syn::Visibility::Public(_)) => {
+                    Some(f.sig.ident.to_string())
+                }
+                _ => None,
+            })
+            .collect();
+        for name in ["hash_password", "verify_password", "require_session"] {
+            assert!(public_fns.iter().any(|f| f == name), "missing {}", name);
+        }
+        let manifest = std::fs::read_to_string(config.project_manifest_path()).unwrap();
+        assert!(manifest.contains("argon2"));
     }
 }
@@ -0,0 +1,254 @@
+use crate::ai_functions::aifunc_database::{print_database_schema, print_fixed_database_schema};
+use crate::helpers::command_line::PrintCommand;
+use crate::helpers::config::workspace_config;
+use crate::helpers::database_schema::{
+    migration_sql, normalize_schema, DatabaseStore, SQLITE_DEPENDENCY,
+};
+use crate::helpers::general::{
+    add_project_dependency, ai_task_request_decoded, save_database_schema, save_migration,
+};
+use crate::models::agent_basic::basic_agent::{AgentState, BasicAgent};
+use crate::models::agents::agent_traits::{
+    progress_from_value, progress_to_value, DatabaseSchema, FactSheet, SpecialFunctions,
+};
+use crate::models::general::error::AutoGptError;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+// Rounds of fixes before the schema is given up on
+const MAX_SCHEMA_FIXES: u8 = 2;
+
+#[derive(Debug)]
+pub struct AgentDatabaseDesigner {
+    attributes: BasicAgent,
+    problems: Option<String>,
+    fix_count: u8,
+}
+
+// Pending problems and fix rounds, kept in run checkpoints
+#[derive(Debug, Serialize, Deserialize)]
+struct DatabaseProgress {
+    problems: Option<String>,
+    fix_count: u8,
+}
+
+impl AgentDatabaseDesigner {
+    pub fn new() -> Self {
+        let attributes = BasicAgent {
+            objective: "Design the database the backend persists its data in".to_string(),
+            position: "Database Designer".to_string(),
+            state: AgentState::Discovery,
+            memory: vec![],
+        };
+
+        Self {
+            attributes,
+            problems: None,
+            fix_count: 0,
+        }
+    }
+
+    fn schema_json(schema: &Option<DatabaseSchema>) -> Result<String, AutoGptError> {
+        serde_json::to_string_pretty(schema).map_err(|source| AutoGptError::Decode {
+            function: "database_schema".to_string(),
+            source,
+        })
+    }
+
+    async fn call_database_schema(
+        &mut self,
+        factsheet: &mut FactSheet,
+    ) -> Result<(), AutoGptError> {
+        let schema = ai_task_request_decoded::<DatabaseSchema>(
+            factsheet.project_description.clone(),
+            &mut self.attributes,
+            get_function_string!(print_database_schema),
+            print_database_schema,
+        )
+        .await?;
+
+        factsheet.database_schema = Some(schema);
+        Ok(())
+    }
+
+    async fn call_fix_database_schema(
+        &mut self,
+        factsheet: &mut FactSheet,
+    ) -> Result<(), AutoGptError> {
+        let msg_context = format!(
+            "PROJECT_DESCRIPTION: {} \n BROKEN_SCHEMA: {} \n PROBLEMS: {} \n",
+            factsheet.project_description,
+            Self::schema_json(&factsheet.database_schema)?,
+            self.problems.as_deref().unwrap_or_default()
+        );
+
+        let schema = ai_task_request_decoded::<DatabaseSchema>(
+            msg_context,
+            &mut self.attributes,
+            get_function_string!(print_fixed_database_schema),
+            print_fixed_database_schema,
+        )
+        .await?;
+
+        factsheet.database_schema = Some(schema);
+        Ok(())
+    }
+
+    // Write the schema and its migration, and what the generated project needs to run it
+    fn save_schema(&self, schema: &DatabaseSchema) -> Result<(), AutoGptError> {
+        let config = workspace_config()?;
+        save_database_schema(&Self::schema_json(&Some(schema.clone()))?)?;
+        save_migration(&migration_sql(schema))?;
+
+        if config.database.store == DatabaseStore::Sqlite {
+            let (name, spec) = SQLITE_DEPENDENCY;
+            if add_project_dependency(name, spec)? {
+                PrintCommand::UnitTest.print_agent_message(
+                    self.attributes.position.as_str(),
+                    format!("Added {} to the project's dependencies", name).as_str(),
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SpecialFunctions for AgentDatabaseDesigner {
+    fn get_attributes_from_agent(&self) -> &BasicAgent {
+        &self.attributes
+    }
+
+    fn get_attributes_from_agent_mut(&mut self) -> &mut BasicAgent {
+        &mut self.attributes
+    }
+
+    fn save_progress(&self) -> Result<Option<serde_json::Value>, AutoGptError> {
+        progress_to_value(
+            &self.attributes.position,
+            &DatabaseProgress {
+                problems: self.problems.clone(),
+                fix_count: self.fix_count,
+            },
+        )
+    }
+
+    fn restore_progress(&mut self, progress: serde_json::Value) -> Result<(), AutoGptError> {
+        let progress: DatabaseProgress = progress_from_value(&self.attributes.position, progress)?;
+        self.problems = progress.problems;
+        self.fix_count = progress.fix_count;
+        Ok(())
+    }
+
+    async fn execute_step(&mut self, factsheet: &mut FactSheet) -> Result<(), AutoGptError> {
+        match &self.attributes.state {
+            AgentState::Discovery => {
+                // Sites without CRUD keep whatever the code template stores
+                let is_crud_required = factsheet
+                    .project_scope
+                    .as_ref()
+                    .is_some_and(|scope| scope.is_crud_required);
+                if !is_crud_required {
+                    PrintCommand::UnitTest.print_agent_message(
+                        self.attributes.position.as_str(),
+                        "No CRUD required, no database to design",
+                    );
+                    self.attributes.state = AgentState::Finished;
+                    return Ok(());
+                }
+
+                self.call_database_schema(factsheet).await?;
+                self.attributes.state = AgentState::UnitTesting;
+            }
+            AgentState::Working => {
+                self.call_fix_database_schema(factsheet).await?;
+                self.attributes.state = AgentState::UnitTesting;
+            }
+            AgentState::UnitTesting => {
+                let Some(schema) = &factsheet.database_schema else {
+                    self.attributes.state = AgentState::Discovery;
+                    return Ok(());
+                };
+
+                match normalize_schema(schema) {
+                    Ok(schema) => {
+                        PrintCommand::UnitTest.print_agent_message(
+                            self.attributes.position.as_str(),
+                            format!("Database schema:\n{}", migration_sql(&schema)).as_str(),
+                        );
+                        self.save_schema(&schema)?;
+                        factsheet.database_schema = Some(schema);
+                        self.attributes.state = AgentState::Finished;
+                    }
+                    Err(problems) => {
+                        let problems = problems.join("\n");
+                        PrintCommand::Issue.print_agent_message(
+                            self.attributes.position.as_str(),
+                            problems.as_str(),
+                        );
+                        self.fix_count += 1;
+                        if self.fix_count > MAX_SCHEMA_FIXES {
+                            return Err(AutoGptError::DatabaseSchema(problems));
+                        }
+                        self.problems = Some(problems);
+                        self.attributes.state = AgentState::Working;
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apis::llm_provider::with_llm_provider;
+    use crate::apis::mock_provider::MockProvider;
+    use crate::helpers::config::{scratch_workspace, with_workspace_config};
+    use crate::helpers::database_schema::DatabaseConfig;
+    use crate::models::agents::agent_traits::ProjectScope;
+    use std::fs;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_database_designer_fixes_and_saves_schema() {
+        let mut agent = AgentDatabaseDesigner::new();
+        let mut factsheet = FactSheet::new("build a website that tracks tasks".to_string());
+        factsheet.project_scope = Some(ProjectScope {
+            is_crud_required: true,
+            is_user_login_and_logout: false,
+            is_external_urls_required: false,
+        });
+
+        let broken = r#"{"entities": [{"name": "Task", "fields": [{"name": "owner_id", "type": "integer", "references": "Owner"}]}]}"#;
+        let fixed =
+            r#"{"entities": [{"name": "Task", "fields": [{"name": "title", "type": "text"}]}]}"#;
+        let provider = Arc::new(
+            MockProvider::new()
+                .with_response("print_database_schema", broken)
+                .with_response("print_fixed_database_schema", fixed),
+        );
+        let (_project, mut config) = scratch_workspace();
+        config.database = DatabaseConfig::default();
+
+        with_workspace_config(
+            config.clone(),
+            with_llm_provider(provider.clone(), agent.execute(&mut factsheet)),
+        )
+        .await
+        .expect("Error executing database designer agent");
+
+        assert_eq!(
+            provider.calls(),
+            vec!["print_database_schema", "print_fixed_database_schema"]
+        );
+        let migration = fs::read_to_string(config.migration_path()).unwrap();
+        assert!(migration.contains("\"title\" TEXT NOT NULL"));
+        assert!(config.database_schema_path().exists());
+        let manifest = fs::read_to_string(config.project_manifest_path()).unwrap();
+        assert!(manifest.contains("rusqlite"));
+    }
+}
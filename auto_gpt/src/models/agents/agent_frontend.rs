@@ -0,0 +1,265 @@
+use crate::ai_functions::aifunc_frontend::{print_fixed_frontend_code, print_frontend_code};
+use crate::helpers::command_line::PrintCommand;
+use crate::helpers::config::workspace_config;
+use crate::helpers::frontend_check::{fetch_targets, unknown_targets, StaticServer};
+use crate::helpers::general::{ai_task_request, save_frontend_code};
+use crate::helpers::response_parsing::extract_html;
+use crate::models::agent_basic::basic_agent::{AgentState, BasicAgent};
+use crate::models::agents::agent_traits::{
+    progress_from_value, progress_to_value, FactSheet, RouteObject, SpecialFunctions,
+};
+use crate::models::general::error::AutoGptError;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+// Rounds of fixes before the frontend is given up on
+const MAX_FRONTEND_FIXES: u8 = 3;
+
+#[derive(Debug)]
+pub struct AgentFrontendDeveloper {
+    attributes: BasicAgent,
+    issues: Option<String>,
+    fix_count: u8,
+}
+
+// Pending issues and fix rounds, kept in run checkpoints
+#[derive(Debug, Serialize, Deserialize)]
+struct FrontendProgress {
+    issues: Option<String>,
+    fix_count: u8,
+}
+
+impl AgentFrontendDeveloper {
+    pub fn new() -> Self {
+        let attributes = BasicAgent {
+            objective: "Develop a static frontend that uses the backend's endpoints".to_string(),
+            position: "Frontend Developer".to_string(),
+            state: AgentState::Discovery,
+            memory: vec![],
+        };
+
+        Self {
+            attributes,
+            issues: None,
+            fix_count: 0,
+        }
+    }
+
+    // The frontend is written against the endpoints the backend developer tested
+    fn api_schema(factsheet: &FactSheet) -> Result<String, AutoGptError> {
+        let routes = factsheet.api_endpoint_schema.as_ref().ok_or_else(|| {
+            AutoGptError::Config(
+                "The frontend needs the API endpoint schema, run the backend agent first"
+                    .to_string(),
+            )
+        })?;
+        serde_json::to_string_pretty(routes).map_err(|source| AutoGptError::Decode {
+            function: "api_endpoint_schema".to_string(),
+            source,
+        })
+    }
+
+    async fn call_initial_frontend_code(
+        &mut self,
+        factsheet: &mut FactSheet,
+    ) -> Result<(), AutoGptError> {
+        let msg_context = format!(
+            "PROJECT_DESCRIPTION: {} \n API_ENDPOINT_SCHEMA: {} \n",
+            factsheet.project_description,
+            Self::api_schema(factsheet)?
+        );
+
+        let ai_response = ai_task_request(
+            msg_context,
+            &mut self.attributes,
+            get_function_string!(print_frontend_code),
+            print_frontend_code,
+        )
+        .await?;
+
+        let html = extract_html(&ai_response);
+        save_frontend_code(&html)?;
+        factsheet.frontend_code = Some(html);
+        Ok(())
+    }
+
+    async fn call_fix_frontend_code(
+        &mut self,
+        factsheet: &mut FactSheet,
+    ) -> Result<(), AutoGptError> {
+        let msg_context = format!(
+            "BROKEN_FRONTEND: {} \n API_ENDPOINT_SCHEMA: {} \n ISSUES: {} \n
+            THIS FUNCTIONS ONLY OUTPUTS CODE. JUST OUTPUT THE CODE.",
+            factsheet.frontend_code.as_deref().unwrap_or_default(),
+            Self::api_schema(factsheet)?,
+            self.issues.as_deref().unwrap_or_default()
+        );
+
+        let ai_response = ai_task_request(
+            msg_context,
+            &mut self.attributes,
+            get_function_string!(print_fixed_frontend_code),
+            print_fixed_frontend_code,
+        )
+        .await?;
+
+        let html = extract_html(&ai_response);
+        save_frontend_code(&html)?;
+        factsheet.frontend_code = Some(html);
+        Ok(())
+    }
+
+    // Serve the page and check that everything it fetches is in the schema
+    async fn check_frontend(&self, routes: &[RouteObject]) -> Result<Vec<String>, AutoGptError> {
+        let config = workspace_config()?;
+        let server = StaticServer::start(&config.frontend_dir()).await?;
+
+        let response = reqwest::get(server.url("/"))
+            .await
+            .map_err(|e| AutoGptError::FrontendCheck(e.to_string()))?;
+        if !response.status().is_success() {
+            return Ok(vec![format!(
+                "index.html is not served, status {}",
+                response.status()
+            )]);
+        }
+        let page = response
+            .text()
+            .await
+            .map_err(|e| AutoGptError::FrontendCheck(e.to_string()))?;
+
+        let targets = fetch_targets(&page);
+        for target in &targets {
+            PrintCommand::UnitTest.print_agent_message(
+                self.attributes.position.as_str(),
+                format!("Frontend fetches: {}", target).as_str(),
+            );
+        }
+
+        let mut issues: Vec<String> = unknown_targets(&targets, routes)
+            .iter()
+            .map(|target| format!("fetch of {} is not an endpoint of the API", target))
+            .collect();
+        if targets.is_empty() && !routes.is_empty() {
+            issues.push("the page does not fetch any endpoint of the API".to_string());
+        }
+        Ok(issues)
+    }
+}
+
+#[async_trait]
+impl SpecialFunctions for AgentFrontendDeveloper {
+    fn get_attributes_from_agent(&self) -> &BasicAgent {
+        &self.attributes
+    }
+
+    fn get_attributes_from_agent_mut(&mut self) -> &mut BasicAgent {
+        &mut self.attributes
+    }
+
+    fn save_progress(&self) -> Result<Option<serde_json::Value>, AutoGptError> {
+        progress_to_value(
+            &self.attributes.position,
+            &FrontendProgress {
+                issues: self.issues.clone(),
+                fix_count: self.fix_count,
+            },
+        )
+    }
+
+    fn restore_progress(&mut self, progress: serde_json::Value) -> Result<(), AutoGptError> {
+        let progress: FrontendProgress = progress_from_value(&self.attributes.position, progress)?;
+        self.issues = progress.issues;
+        self.fix_count = progress.fix_count;
+        Ok(())
+    }
+
+    async fn execute_step(&mut self, factsheet: &mut FactSheet) -> Result<(), AutoGptError> {
+        match &self.attributes.state {
+            AgentState::Discovery => {
+                self.call_initial_frontend_code(factsheet).await?;
+                self.attributes.state = AgentState::UnitTesting;
+            }
+            AgentState::Working => {
+                self.call_fix_frontend_code(factsheet).await?;
+                self.attributes.state = AgentState::UnitTesting;
+            }
+            AgentState::UnitTesting => {
+                PrintCommand::UnitTest.print_agent_message(
+                    self.attributes.position.as_str(),
+                    "Frontend unit testing: serving the page",
+                );
+                let routes = factsheet.api_endpoint_schema.clone().unwrap_or_default();
+                let issues = self.check_frontend(&routes).await?;
+
+                if issues.is_empty() {
+                    PrintCommand::UnitTest.print_agent_message(
+                        self.attributes.position.as_str(),
+                        "Frontend unit testing: every fetch is an endpoint of the API",
+                    );
+                    self.attributes.state = AgentState::Finished;
+                    return Ok(());
+                }
+
+                let issues = issues.join("\n");
+                PrintCommand::Issue
+                    .print_agent_message(self.attributes.position.as_str(), issues.as_str());
+                self.fix_count += 1;
+                if self.fix_count > MAX_FRONTEND_FIXES {
+                    return Err(AutoGptError::FrontendCheck(issues));
+                }
+                self.issues = Some(issues);
+                self.attributes.state = AgentState::Working;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apis::llm_provider::with_llm_provider;
+    use crate::apis::mock_provider::MockProvider;
+    use crate::helpers::config::{scratch_workspace, with_workspace_config};
+    use serde_json::json;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_frontend_fixes_unknown_fetches() {
+        let mut agent = AgentFrontendDeveloper::new();
+        let mut factsheet = FactSheet::new("build a website that tracks tasks".to_string());
+        factsheet.api_endpoint_schema = Some(vec![RouteObject {
+            is_route_dynamic: false,
+            method: "get".to_string(),
+            request_body: json!("None"),
+            response: json!([{"id": "number", "name": "string"}]),
+            route: "/task".to_string(),
+        }]);
+
+        let broken = "```html\n<script>fetch(`${API_BASE_URL}/tasks`)</script>\n```";
+        let fixed = "<script>fetch(`${API_BASE_URL}/task`)</script>";
+        let provider = Arc::new(
+            MockProvider::new()
+                .with_response("print_frontend_code", broken)
+                .with_response("print_fixed_frontend_code", fixed),
+        );
+        let (_project, config) = scratch_workspace();
+
+        with_workspace_config(
+            config.clone(),
+            with_llm_provider(provider.clone(), agent.execute(&mut factsheet)),
+        )
+        .await
+        .expect("Error executing frontend developer agent");
+
+        assert_eq!(
+            provider.calls(),
+            vec!["print_frontend_code", "print_fixed_frontend_code"]
+        );
+        assert_eq!(factsheet.frontend_code.as_deref(), Some(fixed));
+        assert!(config.frontend_dir().join("index.html").exists());
+    }
+}
@@ -0,0 +1,348 @@
+use crate::ai_functions::aifunc_qa::{print_fixed_integration_tests, print_integration_tests};
+use crate::helpers::command_line::{confirm_safe_code, CodeReview, PrintCommand};
+use crate::helpers::config::{workspace_config, WorkspaceConfig};
+use crate::helpers::diagnostics::{compiler_errors, format_errors, parse_cargo_messages};
+use crate::helpers::general::{
+    ai_task_request_rust_code, read_integration_tests, save_integration_tests,
+};
+use crate::helpers::safety_scan::{hosts_of, scan_code};
+use crate::helpers::sandbox::Sandbox;
+use crate::helpers::test_report::{parse_test_output, INTEGRATION_TESTS};
+use crate::models::agent_basic::basic_agent::{AgentState, BasicAgent};
+use crate::models::agents::agent_traits::{
+    progress_from_value, progress_to_value, FactSheet, SpecialFunctions,
+};
+use crate::models::general::error::AutoGptError;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+// Rounds of fixing tests that do not compile
+const MAX_TEST_FIXES: u8 = 3;
+
+// Rounds of failing tests sent back to the backend developer
+const MAX_FAILING_RUNS: u8 = 3;
+
+#[derive(Debug)]
+pub struct AgentQATester {
+    attributes: BasicAgent,
+    // Compile errors of the tests themselves
+    test_errors: Option<String>,
+    fix_count: u8,
+    failing_runs: u8,
+}
+
+// Pending test errors and both retry counts, kept in run checkpoints
+#[derive(Debug, Serialize, Deserialize)]
+struct QaProgress {
+    test_errors: Option<String>,
+    fix_count: u8,
+    failing_runs: u8,
+}
+
+impl AgentQATester {
+    pub fn new() -> Self {
+        let attributes = BasicAgent {
+            objective: "Write and run integration tests for the backend's endpoints".to_string(),
+            position: "QA Tester".to_string(),
+            state: AgentState::Discovery,
+            memory: vec![],
+        };
+
+        Self {
+            attributes,
+            test_errors: None,
+            fix_count: 0,
+            failing_runs: 0,
+        }
+    }
+
+    fn backend_code(factsheet: &FactSheet) -> Result<&str, AutoGptError> {
+        factsheet.backend_code.as_deref().ok_or_else(|| {
+            AutoGptError::Config(
+                "The QA tester needs the backend code, run the backend agent first".to_string(),
+            )
+        })
+    }
+
+    async fn call_integration_tests(
+        &mut self,
+        factsheet: &mut FactSheet,
+    ) -> Result<(), AutoGptError> {
+        let api_schema =
+            serde_json::to_string_pretty(&factsheet.api_endpoint_schema).map_err(|source| {
+                AutoGptError::Decode {
+                    function: "api_endpoint_schema".to_string(),
+                    source,
+                }
+            })?;
+        let msg_context = format!(
+            "BACKEND_CODE: {} \n API_ENDPOINT_SCHEMA: {} \n",
+            Self::backend_code(factsheet)?,
+            api_schema
+        );
+
+        let ai_response = ai_task_request_rust_code(
+            msg_context,
+            &mut self.attributes,
+            get_function_string!(print_integration_tests),
+            print_integration_tests,
+        )
+        .await?;
+
+        save_integration_tests(&ai_response)?;
+        factsheet.integration_tests = Some(ai_response);
+        Ok(())
+    }
+
+    async fn call_fix_integration_tests(
+        &mut self,
+        factsheet: &mut FactSheet,
+    ) -> Result<(), AutoGptError> {
+        let msg_context = format!(
+            "BACKEND_CODE: {} \n BROKEN_TESTS: {} \n ERROR_BUGS: {} \n
+            THIS FUNCTIONS ONLY OUTPUTS CODE. JUST OUTPUT THE CODE.",
+            Self::backend_code(factsheet)?,
+            factsheet.integration_tests.as_deref().unwrap_or_default(),
+            self.test_errors.as_deref().unwrap_or_default()
+        );
+
+        let ai_response = ai_task_request_rust_code(
+            msg_context,
+            &mut self.attributes,
+            get_function_string!(print_fixed_integration_tests),
+            print_fixed_integration_tests,
+        )
+        .await?;
+
+        save_integration_tests(&ai_response)?;
+        factsheet.integration_tests = Some(ai_response);
+        Ok(())
+    }
+
+    // The tests are AI written code too, scanned and confirmed like the backend's
+    // Ok(false) when they do not parse and go back to be fixed
+    fn review_tests(
+        &mut self,
+        config: &WorkspaceConfig,
+        factsheet: &FactSheet,
+    ) -> Result<bool, AutoGptError> {
+        let tests = read_integration_tests()?;
+        let mut allowed_hosts = config.safety.allowed_hosts.clone();
+        if let Some(external_urls) = &factsheet.external_urls {
+            allowed_hosts.extend(hosts_of(external_urls));
+        }
+        let report = match scan_code(&tests, &allowed_hosts) {
+            Ok(report) => report,
+            Err(e) => {
+                self.test_bug(format!("Tests do not parse as Rust: {}", e))?;
+                return Ok(false);
+            }
+        };
+
+        let print_command = match report.findings.is_empty() {
+            true => PrintCommand::UnitTest,
+            false => PrintCommand::Issue,
+        };
+        print_command.print_agent_message(
+            self.attributes.position.as_str(),
+            format!("Safety scan of integration tests:\n{}", report).as_str(),
+        );
+        if config.safety.auto_reject && report.has_high_severity() {
+            return Err(AutoGptError::UnsafeCode(report.to_string()));
+        }
+
+        if !config.non_interactive {
+            println!("{}", tests);
+            if let CodeReview::Reject = confirm_safe_code(&[]) {
+                return Err(AutoGptError::UserAbort(
+                    "Integration tests are not safe to run".to_string(),
+                ));
+            }
+        }
+        Ok(true)
+    }
+
+    // Tests that do not compile are fixed by the QA tester itself
+    fn test_bug(&mut self, errors: String) -> Result<(), AutoGptError> {
+        PrintCommand::Issue.print_agent_message(self.attributes.position.as_str(), &errors);
+        self.fix_count += 1;
+        if self.fix_count > MAX_TEST_FIXES {
+            return Err(AutoGptError::IntegrationTest(errors));
+        }
+        self.test_errors = Some(errors);
+        self.attributes.state = AgentState::Working;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SpecialFunctions for AgentQATester {
+    fn get_attributes_from_agent(&self) -> &BasicAgent {
+        &self.attributes
+    }
+
+    fn get_attributes_from_agent_mut(&mut self) -> &mut BasicAgent {
+        &mut self.attributes
+    }
+
+    fn save_progress(&self) -> Result<Option<serde_json::Value>, AutoGptError> {
+        progress_to_value(
+            &self.attributes.position,
+            &QaProgress {
+                test_errors: self.test_errors.clone(),
+                fix_count: self.fix_count,
+                failing_runs: self.failing_runs,
+            },
+        )
+    }
+
+    fn restore_progress(&mut self, progress: serde_json::Value) -> Result<(), AutoGptError> {
+        let progress: QaProgress = progress_from_value(&self.attributes.position, progress)?;
+        self.test_errors = progress.test_errors;
+        self.fix_count = progress.fix_count;
+        self.failing_runs = progress.failing_runs;
+        Ok(())
+    }
+
+    async fn execute_step(&mut self, factsheet: &mut FactSheet) -> Result<(), AutoGptError> {
+        match &self.attributes.state {
+            AgentState::Discovery => {
+                self.call_integration_tests(factsheet).await?;
+                self.attributes.state = AgentState::UnitTesting;
+            }
+            AgentState::Working => {
+                self.call_fix_integration_tests(factsheet).await?;
+                self.attributes.state = AgentState::UnitTesting;
+            }
+            AgentState::UnitTesting => {
+                PrintCommand::UnitTest.print_agent_message(
+                    self.attributes.position.as_str(),
+                    "Integration testing: running cargo test",
+                );
+
+                let config = workspace_config()?;
+                if !self.review_tests(&config, factsheet)? {
+                    return Ok(());
+                }
+                let sandbox = Sandbox::new(&config.project_dir, &config.sandbox)?;
+                let output = sandbox.test(INTEGRATION_TESTS).await?;
+
+                let errors = compiler_errors(&parse_cargo_messages(&output.stdout));
+                if !errors.is_empty() {
+                    return self.test_bug(format_errors(&errors));
+                }
+                let report = parse_test_output(&output.stdout);
+                if !output.success && report.tests.is_empty() {
+                    return self.test_bug(output.stderr);
+                }
+
+                let failures = report.failures();
+                let print_command = match failures.is_empty() {
+                    true => PrintCommand::UnitTest,
+                    false => PrintCommand::Issue,
+                };
+                print_command.print_agent_message(
+                    self.attributes.position.as_str(),
+                    format!("Integration testing:\n{}", report).as_str(),
+                );
+
+                if failures.is_empty() {
+                    factsheet.test_failures = None;
+                    self.attributes.state = AgentState::Finished;
+                    return Ok(());
+                }
+
+                // Failing tests go to the backend developer, then are run again
+                let failures = failures
+                    .iter()
+                    .map(|failure| failure.to_string())
+                    .collect::<Vec<String>>()
+                    .join("\n");
+                self.failing_runs += 1;
+                if self.failing_runs > MAX_FAILING_RUNS {
+                    return Err(AutoGptError::IntegrationTest(failures));
+                }
+                factsheet.test_failures = Some(failures);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apis::llm_provider::with_llm_provider;
+    use crate::apis::mock_provider::MockProvider;
+    use crate::helpers::config::{scratch_workspace, with_workspace_config, SCRATCH_SERVER};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_qa_fixes_tests_then_reports_failures() {
+        let mut agent = AgentQATester::new();
+        let mut factsheet = FactSheet::new("build a website that tells the time".to_string());
+        factsheet.backend_code = Some(SCRATCH_SERVER.to_string());
+        factsheet.api_endpoint_schema = Some(vec![]);
+
+        let broken = "include!(\"../src/main.rs\");\n\n#[test]\nfn test_time() {\n    let time: u8 = \"noon\";\n}\n";
+        let failing = "include!(\"../src/main.rs\");\n\n#[test]\nfn test_time() {\n    assert_eq!(1 + 1, 3);\n}\n";
+        let provider = Arc::new(
+            MockProvider::new()
+                .with_response("print_integration_tests", broken)
+                .with_response("print_fixed_integration_tests", failing),
+        );
+        let (_project, config) = scratch_workspace();
+
+        // Discovery, compile errors, fixed tests, failing run
+        let steps = async {
+            for _ in 0..4 {
+                agent.execute_step(&mut factsheet).await?;
+            }
+            Ok::<(), AutoGptError>(())
+        };
+        with_workspace_config(config.clone(), with_llm_provider(provider.clone(), steps))
+            .await
+            .expect("Error executing QA tester agent");
+
+        assert_eq!(
+            provider.calls(),
+            vec!["print_integration_tests", "print_fixed_integration_tests"]
+        );
+        assert!(config.integration_tests_path().exists());
+        assert_eq!(agent.attributes.state, AgentState::UnitTesting);
+        let failures = factsheet
+            .test_failures
+            .expect("Failures should be reported");
+        assert!(failures.contains("FAIL test_time"), "{}", failures);
+    }
+
+    #[tokio::test]
+    async fn test_qa_rejects_unsafe_tests_before_running() {
+        let mut agent = AgentQATester::new();
+        let mut factsheet = FactSheet::new("build a website that tells the time".to_string());
+        factsheet.backend_code = Some(SCRATCH_SERVER.to_string());
+        factsheet.api_endpoint_schema = Some(vec![]);
+
+        let unsafe_tests = "include!(\"../src/main.rs\");\n\n#[test]\nfn test_time() {\n    std::process::Command::new(\"sh\").arg(\"-c\").arg(\"true\").status().unwrap();\n}\n";
+        let provider =
+            Arc::new(MockProvider::new().with_response("print_integration_tests", unsafe_tests));
+        let (_project, config) = scratch_workspace();
+
+        let steps = async {
+            agent.execute_step(&mut factsheet).await?;
+            agent.execute_step(&mut factsheet).await
+        };
+        let result =
+            with_workspace_config(config.clone(), with_llm_provider(provider, steps)).await;
+
+        // Rejected before the sandbox, so there are no test results
+        assert!(
+            matches!(result, Err(AutoGptError::UnsafeCode(_))),
+            "{:?}",
+            result
+        );
+        assert_eq!(factsheet.test_failures, None);
+    }
+}
@@ -1,10 +1,31 @@
-use crate::models::agent_basic::basic_agent::BasicAgent;
+use crate::models::agent_basic::basic_agent::{AgentState, BasicAgent};
+use crate::models::general::error::AutoGptError;
 use async_trait::async_trait;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize};
 use std::fmt::Debug;
 
+// LLMs write booleans as "true" and "false" as often as not
+fn bool_or_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum BoolOrString {
+        Bool(bool),
+        String(String),
+    }
+
+    match BoolOrString::deserialize(deserializer)? {
+        BoolOrString::Bool(value) => Ok(value),
+        BoolOrString::String(value) => value
+            .trim()
+            .to_lowercase()
+            .parse()
+            .map_err(|_| serde::de::Error::custom(format!("expected a bool, got {:?}", value))),
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct RouteObject {
+    #[serde(deserialize_with = "bool_or_string")]
     pub is_route_dynamic: bool,
     pub method: String,
     pub request_body: serde_json::Value,
@@ -19,21 +40,200 @@ pub struct ProjectScope {
     pub is_external_urls_required: bool,
 }
 
+// Column of an entity, every entity also gets an integer `id` primary key
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct EntityField {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub field_type: String,
+    #[serde(default, deserialize_with = "bool_or_string")]
+    pub nullable: bool,
+    // Name of the entity this field points to
+    #[serde(default)]
+    pub references: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Entity {
+    pub name: String,
+    pub fields: Vec<EntityField>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DatabaseSchema {
+    pub entities: Vec<Entity>,
+}
+
+// One version of the backend code, numbered from 1
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CodeSnapshot {
+    pub iteration: usize,
+    // `#[ai_function]` that wrote the code, or the rollback that restored it
+    pub source: String,
+    pub code: String,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct FactSheet {
     pub project_description: String,
     pub project_scope: Option<ProjectScope>,
     pub external_urls: Option<Vec<String>>,
+    #[serde(default)]
+    pub database_schema: Option<DatabaseSchema>,
     pub backend_code: Option<String>,
     pub api_endpoint_schema: Option<Vec<RouteObject>>,
+    #[serde(default)]
+    pub frontend_code: Option<String>,
+    // Integration tests of the QA tester, and what of them fails for the backend developer to fix
+    #[serde(default)]
+    pub integration_tests: Option<String>,
+    #[serde(default)]
+    pub test_failures: Option<String>,
+    // Every version of `backend_code`, oldest first
+    #[serde(default)]
+    pub code_snapshots: Vec<CodeSnapshot>,
+}
+
+impl FactSheet {
+    pub fn new(project_description: String) -> Self {
+        Self {
+            project_description,
+            project_scope: None,
+            external_urls: None,
+            database_schema: None,
+            backend_code: None,
+            api_endpoint_schema: None,
+            frontend_code: None,
+            integration_tests: None,
+            test_failures: None,
+            code_snapshots: vec![],
+        }
+    }
+
+    // Replace the backend code, keeping the previous versions
+    pub fn set_backend_code(&mut self, source: &str, code: String) {
+        self.code_snapshots.push(CodeSnapshot {
+            iteration: self.code_snapshots.len() + 1,
+            source: source.to_string(),
+            code: code.clone(),
+        });
+        self.backend_code = Some(code);
+    }
+
+    // Whether the architect found that users log in and out
+    pub fn is_login_required(&self) -> bool {
+        self.project_scope
+            .as_ref()
+            .is_some_and(|scope| scope.is_user_login_and_logout)
+    }
+
+    // Version before the current one
+    pub fn previous_snapshot(&self) -> Option<&CodeSnapshot> {
+        self.code_snapshots.iter().rev().nth(1)
+    }
+
+    // Make the code of an earlier iteration current again, as a new iteration
+    pub fn roll_back_to(&mut self, iteration: usize) -> Option<String> {
+        let code = self
+            .code_snapshots
+            .iter()
+            .find(|snapshot| snapshot.iteration == iteration)?
+            .code
+            .clone();
+        self.set_backend_code(
+            &format!("rollback to iteration {}", iteration),
+            code.clone(),
+        );
+        Some(code)
+    }
+}
+
+// Progress of an agent as checkpoint JSON
+pub fn progress_to_value<T: Serialize>(
+    position: &str,
+    progress: &T,
+) -> Result<Option<serde_json::Value>, AutoGptError> {
+    serde_json::to_value(progress)
+        .map(Some)
+        .map_err(|source| AutoGptError::Decode {
+            function: format!("{} progress", position),
+            source,
+        })
+}
+
+pub fn progress_from_value<T: DeserializeOwned>(
+    position: &str,
+    progress: serde_json::Value,
+) -> Result<T, AutoGptError> {
+    serde_json::from_value(progress).map_err(|source| AutoGptError::Decode {
+        function: format!("{} progress", position),
+        source,
+    })
 }
 
 #[async_trait]
-pub trait SpecialFunctions: Debug {
+pub trait SpecialFunctions: Debug + Send {
     // Used to get the attributes from the agent
     fn get_attributes_from_agent(&self) -> &BasicAgent;
 
+    // Used to change the attributes, e.g. to start an agent in a later state
+    fn get_attributes_from_agent_mut(&mut self) -> &mut BasicAgent;
+
+    // Pending errors and retry counts of the agent, saved with its state in run checkpoints
+    fn save_progress(&self) -> Result<Option<serde_json::Value>, AutoGptError> {
+        Ok(None)
+    }
+
+    // Continue from what `save_progress` saved when a run is resumed
+    fn restore_progress(&mut self, _progress: serde_json::Value) -> Result<(), AutoGptError> {
+        Ok(())
+    }
+
+    // Perform the work of the current state and move to the next one
+    async fn execute_step(&mut self, factsheet: &mut FactSheet) -> Result<(), AutoGptError>;
+
     // Allow agent to perform its functionality
-    async fn execute(&mut self, facsheet: &mut FactSheet)
-        -> Result<(), Box<dyn std::error::Error>>;
+    async fn execute(&mut self, factsheet: &mut FactSheet) -> Result<(), AutoGptError> {
+        // Infinite loop - be careful
+        while self.get_attributes_from_agent().state != AgentState::Finished {
+            self.execute_step(factsheet).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_snapshots_and_rollback() {
+        let mut factsheet = FactSheet::new("build a website that tells the time".to_string());
+        factsheet.set_backend_code("print_backend_webserver_code", "fn main() {}".to_string());
+        factsheet.set_backend_code("print_fixed_code", "fn main() { broken".to_string());
+        assert_eq!(factsheet.previous_snapshot().map(|s| s.iteration), Some(1));
+
+        let restored = factsheet.roll_back_to(1);
+        assert_eq!(restored.as_deref(), Some("fn main() {}"));
+        assert_eq!(factsheet.backend_code.as_deref(), Some("fn main() {}"));
+        assert_eq!(factsheet.code_snapshots.len(), 3);
+        assert_eq!(
+            factsheet.code_snapshots[2].source,
+            "rollback to iteration 1"
+        );
+        assert_eq!(factsheet.roll_back_to(7), None);
+    }
+
+    #[test]
+    fn test_route_object_accepts_string_bools() {
+        let routes: Vec<RouteObject> = serde_json::from_str(
+            r#"[
+              {"route": "/item/{id}", "is_route_dynamic": "true", "method": "get", "request_body": "None", "response": "None"},
+              {"route": "/item", "is_route_dynamic": false, "method": "post", "request_body": "None", "response": "None"}
+            ]"#,
+        )
+        .expect("Routes should parse");
+        assert!(routes[0].is_route_dynamic);
+        assert!(!routes[1].is_route_dynamic);
+    }
 }
@@ -0,0 +1,103 @@
+use std::time::Duration;
+use thiserror::Error;
+
+// Failure of a single LLM call
+#[derive(Debug, Error)]
+pub enum LlmError {
+    #[error("LLM provider is not configured: {0}")]
+    Config(String),
+
+    #[error("Rate limited by LLM provider")]
+    RateLimited { retry_after: Option<Duration> },
+
+    #[error("LLM provider returned server error {status}: {body}")]
+    Server { status: u16, body: String },
+
+    #[error("LLM provider rejected the request with {status}: {body}")]
+    Client { status: u16, body: String },
+
+    #[error("Could not reach LLM provider: {0}")]
+    Transport(String),
+
+    #[error("Unexpected LLM response: {0}")]
+    InvalidResponse(String),
+}
+
+impl LlmError {
+    // Only rate limits, 5xx and network failures are worth another attempt
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::RateLimited { .. } | Self::Server { .. } | Self::Transport(_)
+        )
+    }
+}
+
+// Everything that can stop the agent pipeline
+#[derive(Debug, Error)]
+pub enum AutoGptError {
+    #[error("LLM configuration error: {0}")]
+    Config(String),
+
+    #[error("LLM request failed: {0}")]
+    LlmTransport(#[from] LlmError),
+
+    #[error("Failed to decode LLM response from {function}: {source}")]
+    Decode {
+        function: String,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    #[error("IO error on {path}: {source}")]
+    Io {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("Backend build failed: {0}")]
+    BuildFailure(String),
+
+    #[error("Aborted by user: {0}")]
+    UserAbort(String),
+
+    #[error("Generated code rejected by the safety scan:\n{0}")]
+    UnsafeCode(String),
+
+    #[error("Database schema rejected: {0}")]
+    DatabaseSchema(String),
+
+    #[error("Endpoint test failed: {0}")]
+    EndpointTest(String),
+
+    #[error("Integration tests failed: {0}")]
+    IntegrationTest(String),
+
+    #[error("Frontend check failed: {0}")]
+    FrontendCheck(String),
+
+    #[error("LLM budget exceeded: spent ${spent:.4} of ${budget:.4}")]
+    BudgetExceeded { spent: f64, budget: f64 },
+}
+
+impl AutoGptError {
+    // Helper for `map_err` on file system calls
+    pub fn io(path: impl Into<String>) -> impl FnOnce(std::io::Error) -> Self {
+        let path = path.into();
+        move |source| Self::Io { path, source }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_io_error_keeps_path() {
+        let err = Err::<(), _>(std::io::Error::other("disk full"))
+            .map_err(AutoGptError::io("src/main.rs"))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "IO error on src/main.rs: disk full");
+    }
+}
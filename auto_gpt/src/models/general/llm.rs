@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::ops::Not;
 
 // A message that can be sent to LLM
 #[derive(Debug, Clone, Serialize)]
@@ -12,6 +13,17 @@ pub struct ChatCompletion {
     pub model: String,
     pub messages: Vec<Message>,
     pub temperature: f32,
+    // Ask for server-sent events instead of a single response
+    #[serde(skip_serializing_if = "Not::not")]
+    pub stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<StreamOptions>,
+}
+
+// `include_usage` adds a last event with the token usage of a streamed response
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamOptions {
+    pub include_usage: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -24,7 +36,77 @@ pub struct APIChoice {
     pub message: APIMessage,
 }
 
+// Token counts reported by the provider for one call
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct APIResponse {
     pub choices: Vec<APIChoice>,
+    #[serde(default)]
+    pub usage: Option<TokenUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct APIDelta {
+    #[serde(default)]
+    pub content: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct APIStreamChoice {
+    pub delta: APIDelta,
+}
+
+// One server-sent event of a streamed chat completion
+#[derive(Debug, Deserialize)]
+pub struct APIStreamChunk {
+    #[serde(default)]
+    pub choices: Vec<APIStreamChoice>,
+    #[serde(default)]
+    pub usage: Option<TokenUsage>,
+}
+
+// Content of a chat completion, with its token usage when the provider reports it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatResponse {
+    pub content: String,
+    pub usage: Option<TokenUsage>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OllamaOptions {
+    pub temperature: f32,
+}
+
+// Chat request for Ollama's native `/api/chat` endpoint
+#[derive(Debug, Clone, Serialize)]
+pub struct OllamaChat {
+    pub model: String,
+    pub messages: Vec<Message>,
+    pub stream: bool,
+    pub options: OllamaOptions,
+}
+
+impl OllamaChat {
+    pub fn new(model: String, messages: Vec<Message>, temperature: f32, stream: bool) -> Self {
+        Self {
+            model,
+            messages,
+            stream,
+            options: OllamaOptions { temperature },
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OllamaResponse {
+    pub message: APIMessage,
+    #[serde(default)]
+    pub prompt_eval_count: Option<u64>,
+    #[serde(default)]
+    pub eval_count: Option<u64>,
 }
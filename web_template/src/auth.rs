@@ -0,0 +1,129 @@
+// Authentication for generated servers - copied into the project unchanged, never written by the LLM
+//
+// Passwords are hashed with argon2, logins hand out random session tokens,
+// and routes wrapped in `from_fn(auth::require_session)` answer 401 without a valid
+// `Authorization: Bearer <token>` header.
+
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::error::{ErrorInternalServerError, ErrorUnauthorized};
+use actix_web::http::header;
+use actix_web::middleware::Next;
+use actix_web::{web, Error, FromRequest, HttpMessage, HttpRequest, HttpResponse};
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use std::collections::HashMap;
+use std::future::{ready, Ready};
+use std::sync::Mutex;
+
+// Hash to store instead of the password
+pub fn hash_password(password: &str) -> Result<String, Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|_| ErrorInternalServerError("Failed to hash password"))
+}
+
+// Whether the password is the one the stored hash was made from
+pub fn verify_password(password: &str, password_hash: &str) -> bool {
+    match PasswordHash::new(password_hash) {
+        Ok(parsed) => Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+// Logged in users by session token, registered with `App::app_data(web::Data::new(Sessions::new()))`
+#[derive(Debug, Default)]
+pub struct Sessions {
+    tokens: Mutex<HashMap<String, u64>>,
+}
+
+impl Sessions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // New session of a user, the token goes to the client
+    pub fn create(&self, user_id: u64) -> String {
+        let mut bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut bytes);
+        let token: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        self.tokens
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(token.clone(), user_id);
+        token
+    }
+
+    pub fn user_id(&self, token: &str) -> Option<u64> {
+        self.tokens
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(token)
+            .copied()
+    }
+
+    // Logout
+    pub fn revoke(&self, token: &str) {
+        self.tokens
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(token);
+    }
+}
+
+// Token of the `Authorization: Bearer <token>` header
+pub fn bearer_token(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(|token| token.trim().to_string())
+}
+
+// User of the request's session, set by `require_session`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AuthenticatedUser {
+    pub user_id: u64,
+}
+
+impl FromRequest for AuthenticatedUser {
+    type Error = Error;
+    type Future = Ready<Result<Self, Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(
+            req.extensions()
+                .get::<AuthenticatedUser>()
+                .copied()
+                .ok_or_else(|| ErrorUnauthorized("Login required")),
+        )
+    }
+}
+
+// Middleware for protected routes: `.wrap(actix_web::middleware::from_fn(auth::require_session))`
+// Anonymous requests get a 401 response, not an error, so tests see the status
+pub async fn require_session<B: MessageBody>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, Error> {
+    let user_id = match (
+        req.app_data::<web::Data<Sessions>>(),
+        bearer_token(req.request()),
+    ) {
+        (Some(sessions), Some(token)) => sessions.user_id(&token),
+        _ => None,
+    };
+    let Some(user_id) = user_id else {
+        let response = HttpResponse::Unauthorized().body("Login required");
+        return Ok(req.into_response(response).map_into_right_body());
+    };
+
+    req.extensions_mut().insert(AuthenticatedUser { user_id });
+    next.call(req).await.map(ServiceResponse::map_into_left_body)
+}
@@ -154,6 +154,12 @@ async fn main() -> std::io::Result<()> {
         db: Mutex::new(db)
     });
 
+    // The port is chosen by whoever starts the server
+    let port: u16 = std::env::var("PORT")
+        .ok()
+        .and_then(|port| port.parse().ok())
+        .unwrap_or(8080);
+
     HttpServer::new(move || {
         App::new()
             .wrap(
@@ -176,7 +182,7 @@ async fn main() -> std::io::Result<()> {
             .route("/register", web::post().to(register))
             .route("/login", web::post().to(login))
     })
-    .bind("127.0.0.1:8080")?
+    .bind(("127.0.0.1", port))?
     .run()
     .await
 }
\ No newline at end of file
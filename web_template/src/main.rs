@@ -1,4 +1,3 @@
-```rust
 use actix_cors::Cors;
 use actix_web::{http::header, web, App, HttpServer, HttpResponse, Responder};
 use chrono::Utc;
@@ -18,6 +17,12 @@ async fn get_current_time() -> impl Responder {
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
+    // The port is chosen by whoever starts the server
+    let port: u16 = std::env::var("PORT")
+        .ok()
+        .and_then(|port| port.parse().ok())
+        .unwrap_or(8080);
+
     HttpServer::new(|| {
         App::new()
             .wrap(
@@ -33,8 +38,7 @@ async fn main() -> std::io::Result<()> {
             )
             .route("/time", web::get().to(get_current_time))
     })
-    .bind("127.0.0.1:8080")?
+    .bind(("127.0.0.1", port))?
     .run()
     .await
 }
-```
\ No newline at end of file